@@ -7,8 +7,8 @@ extern crate bitap_reference as bref;
 use criterion::black_box;
 use criterion::Criterion;
 
-static PATTERN: &'static str = "him";
-static TEXT: &'static str = r#"
+static PATTERN: &str = "him";
+static TEXT: &str = r#"
 "Then be so kind," urged Miss Manette, "as to leave us here. You
 see how composed he has become, and you cannot be afraid to leave
 him with me now. Why should you be? If you will lock the door to
@@ -53,13 +53,32 @@ responded to his daughter's drawing her arm through his, and took —
 and kept — her hand in both his own.
 "#;
 
+// Mask lookups for characters outside of Latin-1 take a slower path, so this
+// makes sure that doesn't regress too badly.
+static UNICODE_PATTERN: &str = "семья";
+static UNICODE_TEXT: &str = r#"
+Все счастливые семьи похожи друг на друга, каждая несчастливая семья
+несчастлива по-своему. Все смешалось в доме Облонских. Жена узнала, что
+муж был в связи с бывшею в их доме француженкою-гувернанткой, и объявила
+мужу, что не может жить с ним в одном доме. Положение это продолжалось
+уже третий день и мучительно чувствовалось и самими супругами, и всеми
+членами семьи, и домочадцами. Все члены семьи и домочадцы чувствовали,
+что нет смысла в их сожительстве и что на каждом постоялом дворе случайно
+сошедшиеся люди более связаны между собой, чем они, члены семьи и
+домочадцы Облонских. Жена не выходила из своих комнат, мужа третий день
+не было дома. Дети бегали по всему дому, как потерянные; англичанка
+поссорилась с экономкой и написала записку приятельнице, прося приискать
+ей новое место; повар ушел еще вчера со двора, во время обеда; черная
+кухарка и кучер просили расчета.
+"#;
+
 fn bench_find(c: &mut Criterion) {
     c.bench_function("ref::find", move |b| {
-        b.iter(|| bref::find(black_box(PATTERN), black_box(&TEXT)).unwrap())
+        b.iter(|| bref::find(black_box(PATTERN), black_box(TEXT)).unwrap())
     });
     let pattern = bitap::Pattern::new(PATTERN).unwrap();
     c.bench_function("bitap::find", move |b| {
-        b.iter(|| pattern.find(black_box(&TEXT)).collect::<Vec<_>>())
+        b.iter(|| pattern.find(black_box(TEXT)).collect::<Vec<_>>())
     });
     c.bench_function("String::match_indices", move |b| {
         b.iter(|| {
@@ -72,13 +91,13 @@ fn bench_find(c: &mut Criterion) {
 
 fn bench_lev(c: &mut Criterion) {
     c.bench_function("ref::lev", move |b| {
-        b.iter(|| bref::lev(black_box(PATTERN), black_box(&TEXT), black_box(2)).unwrap())
+        b.iter(|| bref::lev(black_box(PATTERN), black_box(TEXT), black_box(2)).unwrap())
     });
     let pattern = bitap::Pattern::new(PATTERN).unwrap();
     c.bench_function("bitap::lev", move |b| {
         b.iter(|| {
             pattern
-                .lev(black_box(&TEXT), black_box(2))
+                .lev(black_box(TEXT), black_box(2))
                 .collect::<Vec<_>>()
         })
     });
@@ -86,7 +105,28 @@ fn bench_lev(c: &mut Criterion) {
     c.bench_function("bitap::lev_static", move |b| {
         b.iter(|| {
             pattern
-                .lev_static(black_box(&TEXT), bitap::StaticMaxDistance::Two)
+                .lev_static(black_box(TEXT), bitap::StaticMaxDistance::Two)
+                .collect::<Vec<_>>()
+        })
+    });
+    let pattern = bitap::PatternBuilder::new(PATTERN)
+        .match_start(bitap::Boundary::Word)
+        .match_end(bitap::Boundary::Word)
+        .build()
+        .unwrap();
+    c.bench_function("bitap::lev (whole words)", move |b| {
+        b.iter(|| {
+            pattern
+                .lev(black_box(TEXT), black_box(2))
+                .collect::<Vec<_>>()
+        })
+    });
+    let pattern = bitap::Pattern::new(PATTERN).unwrap();
+    c.bench_function("bitap::lev_myers", move |b| {
+        b.iter(|| {
+            pattern
+                .lev_myers(black_box(TEXT), black_box(2))
+                .unwrap()
                 .collect::<Vec<_>>()
         })
     });
@@ -94,13 +134,13 @@ fn bench_lev(c: &mut Criterion) {
 
 fn bench_osa(c: &mut Criterion) {
     c.bench_function("ref::osa", move |b| {
-        b.iter(|| bref::osa(black_box(PATTERN), black_box(&TEXT), black_box(2)).unwrap())
+        b.iter(|| bref::osa(black_box(PATTERN), black_box(TEXT), black_box(2)).unwrap())
     });
     let pattern = bitap::Pattern::new(PATTERN).unwrap();
     c.bench_function("bitap::osa", move |b| {
         b.iter(|| {
             pattern
-                .osa(black_box(&TEXT), black_box(2))
+                .osa(black_box(TEXT), black_box(2))
                 .collect::<Vec<_>>()
         })
     });
@@ -108,11 +148,143 @@ fn bench_osa(c: &mut Criterion) {
     c.bench_function("bitap::osa_static", move |b| {
         b.iter(|| {
             pattern
-                .osa_static(black_box(&TEXT), bitap::StaticMaxDistance::Two)
+                .osa_static(black_box(TEXT), bitap::StaticMaxDistance::Two)
+                .collect::<Vec<_>>()
+        })
+    });
+    let pattern = bitap::Pattern::new(PATTERN).unwrap();
+    c.bench_function("bitap::osa_myers", move |b| {
+        b.iter(|| {
+            pattern
+                .osa_myers(black_box(TEXT), black_box(2))
+                .unwrap()
                 .collect::<Vec<_>>()
         })
     });
 }
 
-criterion_group!(benches, bench_find, bench_lev, bench_osa);
+fn bench_unicode(c: &mut Criterion) {
+    let pattern = bitap::Pattern::new(UNICODE_PATTERN).unwrap();
+    c.bench_function("bitap::find (unicode)", move |b| {
+        b.iter(|| pattern.find(black_box(UNICODE_TEXT)).collect::<Vec<_>>())
+    });
+    c.bench_function("String::match_indices (unicode)", move |b| {
+        b.iter(|| {
+            black_box(UNICODE_TEXT)
+                .match_indices(black_box(UNICODE_PATTERN))
+                .collect::<Vec<_>>()
+        })
+    });
+    let pattern = bitap::Pattern::new(UNICODE_PATTERN).unwrap();
+    c.bench_function("bitap::lev (unicode)", move |b| {
+        b.iter(|| {
+            pattern
+                .lev(black_box(UNICODE_TEXT), black_box(2))
+                .collect::<Vec<_>>()
+        })
+    });
+}
+
+// Searching for a lot of short patterns at once, like product codes.
+fn bench_set(c: &mut Criterion) {
+    let patterns = TEXT
+        .split_whitespace()
+        .filter(|w| w.len() > 3)
+        .take(50)
+        .collect::<Vec<_>>();
+    let set = bitap::PatternSet::new(&patterns).unwrap();
+    c.bench_function("bitap::PatternSet::lev", move |b| {
+        b.iter(|| set.lev(black_box(TEXT), black_box(1)).collect::<Vec<_>>())
+    });
+    let compiled = patterns
+        .iter()
+        .map(|p| bitap::Pattern::new(p).unwrap())
+        .collect::<Vec<_>>();
+    c.bench_function("bitap::lev (50 patterns)", move |b| {
+        b.iter(|| {
+            compiled
+                .iter()
+                .map(|p| p.lev(black_box(TEXT), black_box(1)).count())
+                .sum::<usize>()
+        })
+    });
+}
+
+// Comparing every word of the text with a misspelling, like a spell checker.
+fn bench_distance(c: &mut Criterion) {
+    let words = TEXT.split_whitespace().collect::<Vec<_>>();
+    let words2 = words.clone();
+    c.bench_function("strsim::levenshtein", move |b| {
+        b.iter(|| {
+            words
+                .iter()
+                .filter(|w| {
+                    let d = bref::baseline::distance(
+                        black_box("daugther"),
+                        w,
+                        bref::baseline::DistanceFn::Levenshtein,
+                    );
+                    d <= 2
+                })
+                .count()
+        })
+    });
+    c.bench_function("bitap::within_distance", move |b| {
+        b.iter(|| {
+            words2
+                .iter()
+                .filter(|w| bitap::within_distance(black_box("daugther"), w, 2))
+                .count()
+        })
+    });
+}
+
+// Every engine that can handle each combination of pattern length and max
+// distance, to find where `Engine::Auto` should switch between them.
+fn bench_engines(c: &mut Criterion) {
+    let patterns = [
+        PATTERN,
+        "the daughter",
+        "the shoemaker's bench (there was nothing",
+        "mysteries of his mind, in the scared blank wonder of his face. Whether he knew what had happened",
+    ];
+    for p in patterns.iter() {
+        let len = p.chars().count();
+        for &k in [0, 1, 2, 3, 4, 6, 8].iter().filter(|&&k| k < len) {
+            let engines = [
+                ("exact", bitap::Engine::Exact),
+                ("static", bitap::Engine::Static),
+                ("dynamic", bitap::Engine::Dynamic),
+                ("myers", bitap::Engine::Myers),
+                ("auto", bitap::Engine::Auto),
+            ];
+            for &(name, engine) in engines.iter() {
+                let pattern = bitap::PatternBuilder::new(p)
+                    .engine(engine)
+                    .build()
+                    .unwrap();
+                // Engines that can't handle the distance just fall back to
+                // another one, so there's no point timing them.
+                if engine != bitap::Engine::Auto && pattern.engine_for(k) != engine {
+                    continue;
+                }
+                let id = format!("bitap::lev {} (length {}, k {})", name, len, k);
+                c.bench_function(&id, move |b| {
+                    b.iter(|| pattern.lev(black_box(TEXT), black_box(k)).count())
+                });
+            }
+        }
+    }
+}
+
+criterion_group!(
+    benches,
+    bench_find,
+    bench_lev,
+    bench_osa,
+    bench_unicode,
+    bench_set,
+    bench_distance,
+    bench_engines
+);
 criterion_main!(benches);
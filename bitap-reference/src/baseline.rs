@@ -3,10 +3,24 @@ use super::*;
 use std::cmp;
 use strsim;
 
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub enum DistanceFn {
     Levenshtein,
     DamerauLevenshtein,
     OptimalStringAlignment,
+    Hamming,
+}
+
+/// Returns the edit distance between two whole strings. Hamming distance is
+/// only defined for strings of the same length, so other strings are
+/// infinitely far apart.
+pub fn distance(a: &str, b: &str, distance_fn: DistanceFn) -> usize {
+    match distance_fn {
+        DistanceFn::Levenshtein => strsim::levenshtein(a, b),
+        DistanceFn::DamerauLevenshtein => strsim::damerau_levenshtein(a, b),
+        DistanceFn::OptimalStringAlignment => strsim::osa_distance(a, b),
+        DistanceFn::Hamming => strsim::hamming(a, b).unwrap_or(usize::MAX),
+    }
 }
 
 /// The baseline functions should be functionally the same as the reference
@@ -27,9 +41,11 @@ pub fn baseline(
     // came up in the past where my "reference" impl had the same flaws as my
     // production version.
 
-    // Enforce bitap-specific pattern size limits.
+    // Unlike the reference, don't enforce the single word pattern size limit;
+    // the bitap crate can search for longer patterns using multiple words,
+    // and those need checking too. Empty patterns are still invalid.
     let pattern_len = pattern.chars().count();
-    if !pattern_length_is_valid(pattern_len) {
+    if pattern_len == 0 {
         return Err(ERR_INVALID_PATTERN);
     }
 
@@ -50,11 +66,7 @@ pub fn baseline(
         let mut best_distance: usize = max_distance + 1;
         for j in start..=i {
             let sub_text: String = text_chars[j..=i].iter().collect();
-            let distance = match distance_fn {
-                DistanceFn::Levenshtein => strsim::levenshtein(pattern, &sub_text),
-                DistanceFn::DamerauLevenshtein => strsim::damerau_levenshtein(pattern, &sub_text),
-                DistanceFn::OptimalStringAlignment => strsim::osa_distance(pattern, &sub_text),
-            };
+            let distance = distance(pattern, &sub_text, distance_fn);
             if distance < best_distance {
                 best_distance = distance;
             }
@@ -90,3 +102,7 @@ pub fn damerau(pattern: &str, text: &str, k: usize) -> BitapResult {
 pub fn osa(pattern: &str, text: &str, k: usize) -> BitapResult {
     baseline(pattern, text, k, DistanceFn::OptimalStringAlignment)
 }
+
+pub fn hamming(pattern: &str, text: &str, k: usize) -> BitapResult {
+    baseline(pattern, text, k, DistanceFn::Hamming)
+}
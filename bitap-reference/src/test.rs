@@ -212,6 +212,34 @@ fn test_damerau_levenshtein() {
     }
 }
 
+#[test]
+fn test_hamming() {
+    #[rustfmt::skip]
+    let cases: Vec<TestCase> = vec![
+        TestCase::new(
+            "abcde",
+            "bcde abde abccde abzde abdce",
+            "                     1      ",
+            1,
+        ),
+        TestCase::new(
+            "abc",
+            "ab abc",
+            "  1  0",
+            2,
+        ),
+    ];
+    // Like damerau-levenshtein, there's no bitap reference implementation.
+    for case in cases.iter() {
+        let result = baseline::hamming(&case.pattern, &case.text, case.max_distance)
+            .unwrap()
+            .iter()
+            .map(|m| (m.distance, m.end))
+            .collect::<Vec<_>>();
+        assert_eq!(result, case.expected_matches);
+    }
+}
+
 #[test]
 fn test_osa() {
     let impls: Vec<BitapImpl> = vec![osa, baseline::osa];
@@ -0,0 +1,162 @@
+use core::ops::{BitAnd, BitOr, BitXor, Not, Shl};
+
+/// A fixed-size vector of bits that bitap can use as its state and pattern
+/// mask representation.
+///
+/// The iterator adapters and `Pattern` are generic over this trait, so the
+/// same algorithm works whether the masks fit in a single `u32`, `u64`,
+/// `u128` or `usize`, or need to be spread over several machine words with
+/// `Wide`. A pattern must be strictly shorter than `BITS`, because the bit
+/// just past the end of the pattern is used to detect matches.
+pub trait BitVector:
+    Copy
+    + Eq
+    + BitAnd<Output = Self>
+    + BitOr<Output = Self>
+    + BitXor<Output = Self>
+    + Not<Output = Self>
+    + Shl<usize, Output = Self>
+{
+    /// The number of bits in the vector.
+    const BITS: usize;
+
+    /// Returns a vector with every bit cleared.
+    fn zero() -> Self;
+
+    /// Returns a vector with only the least significant bit set.
+    fn one() -> Self;
+
+    /// Adds two vectors as if they were unsigned integers, throwing away
+    /// whatever carries out of the top bit.
+    fn wrapping_add(self, rhs: Self) -> Self;
+}
+
+macro_rules! impl_word {
+    ($($word:ty),*) => {
+        $(
+            impl BitVector for $word {
+                const BITS: usize = <$word>::BITS as usize;
+
+                #[inline]
+                fn zero() -> Self {
+                    0
+                }
+
+                #[inline]
+                fn one() -> Self {
+                    1
+                }
+
+                #[inline]
+                fn wrapping_add(self, rhs: Self) -> Self {
+                    <$word>::wrapping_add(self, rhs)
+                }
+            }
+        )*
+    };
+}
+
+impl_word!(u32, u64, u128, usize);
+
+/// A bit vector made up of `N` 64 bit words, for patterns too long to fit in
+/// a single machine word.
+///
+/// Word zero holds the least significant bits, and shifts carry bits from
+/// each word into the next, so it behaves just like one really wide integer.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Wide<const N: usize>([u64; N]);
+
+impl<const N: usize> BitVector for Wide<N> {
+    const BITS: usize = 64 * N;
+
+    #[inline]
+    fn zero() -> Self {
+        Wide([0; N])
+    }
+
+    #[inline]
+    fn one() -> Self {
+        let mut words = [0; N];
+        words[0] = 1;
+        Wide(words)
+    }
+
+    #[inline]
+    fn wrapping_add(mut self, rhs: Self) -> Self {
+        let mut carry = false;
+        for (a, b) in self.0.iter_mut().zip(rhs.0.iter()) {
+            let (sum, overflow) = a.overflowing_add(*b);
+            let (sum, carried) = sum.overflowing_add(carry as u64);
+            *a = sum;
+            carry = overflow || carried;
+        }
+        self
+    }
+}
+
+impl<const N: usize> BitAnd for Wide<N> {
+    type Output = Self;
+
+    #[inline]
+    fn bitand(mut self, rhs: Self) -> Self {
+        for (a, b) in self.0.iter_mut().zip(rhs.0.iter()) {
+            *a &= b;
+        }
+        self
+    }
+}
+
+impl<const N: usize> BitOr for Wide<N> {
+    type Output = Self;
+
+    #[inline]
+    fn bitor(mut self, rhs: Self) -> Self {
+        for (a, b) in self.0.iter_mut().zip(rhs.0.iter()) {
+            *a |= b;
+        }
+        self
+    }
+}
+
+impl<const N: usize> BitXor for Wide<N> {
+    type Output = Self;
+
+    #[inline]
+    fn bitxor(mut self, rhs: Self) -> Self {
+        for (a, b) in self.0.iter_mut().zip(rhs.0.iter()) {
+            *a ^= b;
+        }
+        self
+    }
+}
+
+impl<const N: usize> Not for Wide<N> {
+    type Output = Self;
+
+    #[inline]
+    fn not(mut self) -> Self {
+        for a in self.0.iter_mut() {
+            *a = !*a;
+        }
+        self
+    }
+}
+
+impl<const N: usize> Shl<usize> for Wide<N> {
+    type Output = Self;
+
+    #[inline]
+    fn shl(self, n: usize) -> Self {
+        let (offset, bits) = (n / 64, n % 64);
+        let mut words = [0; N];
+        for (src, word) in words.iter_mut().skip(offset).enumerate() {
+            *word = self.0[src] << bits;
+            // Carry the bits that were shifted off the top of the previous
+            // word into the bottom of this one.
+            if bits > 0 && src > 0 {
+                *word |= self.0[src - 1] >> (64 - bits);
+            }
+        }
+        Wide(words)
+    }
+}
@@ -0,0 +1,145 @@
+use core::str::CharIndices;
+
+use crate::masks::CharMasks;
+use crate::step::Step;
+use crate::BitVector;
+
+/// Where a match is allowed to start or end. See `PatternBuilder::match_start`
+/// and `PatternBuilder::match_end`.
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+pub enum Boundary {
+    /// Anywhere in the text.
+    Anywhere,
+    /// Only at the edge of a word, so not right next to a word character on
+    /// the outside of the match. The start and end of the text count as edges
+    /// of a word.
+    Word,
+    /// Only at the start (or end) of the text.
+    Text,
+}
+
+/// Where a pattern's matches are allowed to start and end.
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct Bounds {
+    pub(crate) start: Boundary,
+    pub(crate) end: Boundary,
+    pub(crate) is_word: fn(char) -> bool,
+}
+
+impl Default for Bounds {
+    fn default() -> Bounds {
+        Bounds {
+            start: Boundary::Anywhere,
+            end: Boundary::Anywhere,
+            is_word: char::is_alphanumeric,
+        }
+    }
+}
+
+impl Bounds {
+    /// Returns whether a match can start right after `prev`, where `None`
+    /// is the start of the text.
+    #[inline]
+    pub(crate) fn can_start_after(&self, prev: Option<char>) -> bool {
+        self.allows(self.start, prev)
+    }
+
+    /// Returns whether a match can end right before `next`, where `None` is
+    /// the end of the text.
+    #[inline]
+    pub(crate) fn can_end_before(&self, next: Option<char>) -> bool {
+        self.allows(self.end, next)
+    }
+
+    #[inline]
+    fn allows(&self, boundary: Boundary, outside: Option<char>) -> bool {
+        match (boundary, outside) {
+            (Boundary::Anywhere, _) | (_, None) => true,
+            (Boundary::Word, Some(c)) => !(self.is_word)(c),
+            (Boundary::Text, Some(_)) => false,
+        }
+    }
+}
+
+/// Runs a search over the characters of a string, returning what it found
+/// along with the index of the character each match ends on and the byte
+/// offset just past it.
+///
+/// Where a match can start is passed to the search with each character, and
+/// where it can end just needs to look one character ahead.
+pub(crate) struct BoundedMatches<'a, B, S> {
+    masks: &'a CharMasks<B>,
+    chars: CharIndices<'a>,
+    position: usize,
+    bounds: Bounds,
+    /// The last character read, or `None` at the start of the text.
+    prev: Option<char>,
+    search: S,
+}
+
+impl<'a, B, S> BoundedMatches<'a, B, S> {
+    pub(crate) fn new(
+        masks: &'a CharMasks<B>,
+        text: &'a str,
+        bounds: Bounds,
+        search: S,
+    ) -> BoundedMatches<'a, B, S> {
+        BoundedMatches {
+            masks,
+            chars: text.char_indices(),
+            position: 0,
+            bounds,
+            prev: None,
+            search,
+        }
+    }
+}
+
+impl<'a, B: BitVector, S: Step<B>> BoundedMatches<'a, B, S> {
+    /// Runs the search up to the next match, given whether a match can start
+    /// after the character before and end before the one after.
+    #[inline]
+    fn find_next<F, G>(
+        &mut self,
+        can_start_after: F,
+        can_end_before: G,
+    ) -> Option<(S::Output, usize, usize)>
+    where
+        F: Fn(Option<char>) -> bool,
+        G: Fn(Option<char>) -> bool,
+    {
+        let mut prev = self.prev;
+        while let Some((offset, c)) = self.chars.next() {
+            let end = self.position;
+            self.position += 1;
+            let found = self.search.step(self.masks.get(c), can_start_after(prev));
+            prev = Some(c);
+            if let Some(found) = found {
+                if can_end_before(self.chars.as_str().chars().next()) {
+                    self.prev = prev;
+                    return Some((found, end, offset + c.len_utf8()));
+                }
+            }
+        }
+        None
+    }
+}
+
+impl<'a, B: BitVector, S: Step<B>> Iterator for BoundedMatches<'a, B, S> {
+    type Item = (S::Output, usize, usize);
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        // This is the inner loop of almost every search, and asking the
+        // bounds about every character is slow enough to notice, so the usual
+        // case gets a loop of its own.
+        let bounds = self.bounds;
+        match (bounds.start, bounds.end) {
+            (Boundary::Anywhere, Boundary::Anywhere) => self.find_next(|_| true, |_| true),
+            _ => self.find_next(
+                |prev| bounds.can_start_after(prev),
+                |next| bounds.can_end_before(next),
+            ),
+        }
+    }
+}
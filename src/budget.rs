@@ -0,0 +1,246 @@
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cmp;
+
+use crate::step::{transposition_start, Step};
+use crate::{check_length, BitVector, Error};
+
+/// The most combinations of edit counts that a budget for `budgeted` can
+/// allow, counting zero of each kind. Every combination is a state vector
+/// that gets updated for each character of the text.
+pub const MAX_BUDGET_STATES: usize = 1 << 16;
+
+/// A number of each kind of edit. Used both for the most of each that a
+/// search allows, and for how many of each a match actually needed.
+///
+/// As a budget, the product of each count plus one has to be at most
+/// `MAX_BUDGET_STATES`, after deletions and substitutions are capped to the
+/// length of the pattern and transpositions to half of it. Insertions
+/// aren't capped, since a match can have any number of them.
+#[derive(Copy, Clone, Debug, Default, Eq, Hash, PartialEq)]
+pub struct EditCounts {
+    /// Characters of the text that aren't in the pattern.
+    pub insertions: usize,
+    /// Characters of the pattern that aren't in the text.
+    pub deletions: usize,
+    /// Characters of the pattern that were replaced by another one.
+    pub substitutions: usize,
+    /// Pairs of neighbouring characters that were swapped.
+    pub transpositions: usize,
+}
+
+impl EditCounts {
+    /// Returns the total number of edits.
+    #[inline]
+    pub fn total(&self) -> usize {
+        self.insertions + self.deletions + self.substitutions + self.transpositions
+    }
+}
+
+/// BudgetMatch is a match found by `budgeted`, along with how many of each
+/// kind of edit it took.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct BudgetMatch {
+    /// The edits used by this match.
+    pub edits: EditCounts,
+    /// The index that this match _ends_ on.
+    pub end: usize,
+}
+
+impl BudgetMatch {
+    /// Returns the edit distance for this match, counting every kind of edit
+    /// as one.
+    #[inline]
+    pub fn distance(&self) -> usize {
+        self.edits.total()
+    }
+}
+
+/// TextBudgetMatch is a match found by `Pattern::budgeted`, along with where
+/// it ends in bytes. See `TextMatch`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct TextBudgetMatch {
+    /// The edits used by this match.
+    pub edits: EditCounts,
+    /// The index of the character that this match _ends_ on.
+    pub end: usize,
+    /// The byte offset just past the last character of the match, so that
+    /// `&text[..byte_end]` ends with the match.
+    pub byte_end: usize,
+}
+
+impl TextBudgetMatch {
+    /// Returns the edit distance for this match, counting every kind of edit
+    /// as one.
+    #[inline]
+    pub fn distance(&self) -> usize {
+        self.edits.total()
+    }
+}
+
+impl From<TextBudgetMatch> for BudgetMatch {
+    #[inline]
+    fn from(m: TextBudgetMatch) -> BudgetMatch {
+        BudgetMatch {
+            edits: m.edits,
+            end: m.end,
+        }
+    }
+}
+
+/// Iterator adapter for implementing bitap with a separate budget for each
+/// kind of edit over an iterator of pattern masks. Transpositions are
+/// restricted, like in `optimal_string_alignment`.
+///
+/// This is `levenshtein`, but instead of a row of states for each total
+/// number of edits, there's one for every combination of edit counts within
+/// the budget. That makes it as slow as the product of the budgets (plus
+/// one) rather than their sum, so keep them small. Will fail if that
+/// product is more than `MAX_BUDGET_STATES`.
+///
+/// When several combinations of edits match at the same place, the one with
+/// the fewest edits in total is returned, and ties go to fewer insertions,
+/// then fewer deletions, then fewer substitutions.
+///
+/// A match has to use at least one character of the text, so deleting the
+/// whole pattern doesn't count, even when the budget allows it.
+pub fn budgeted<B: BitVector, I: Iterator<Item = B>>(
+    mask_iter: I,
+    pattern_length: usize,
+    budget: EditCounts,
+) -> Result<impl Iterator<Item = BudgetMatch>, Error> {
+    check_length::<B>(pattern_length)?;
+    let budget = clamp_budget(budget, pattern_length)?;
+    let mut search = Budgeted::new(pattern_length, budget);
+    let matches = mask_iter.enumerate().filter_map(move |(i, mask)| {
+        let edits = search.step(mask, true)?;
+        Some(BudgetMatch { edits, end: i })
+    });
+    Ok(matches)
+}
+
+/// The state of `budgeted`, for a budget that's already been clamped.
+pub(crate) struct Budgeted<B> {
+    // Every combination of edit counts, in the order described above, and
+    // the distance between neighbouring combinations for each kind of edit.
+    states: Vec<EditCounts>,
+    insert_stride: usize,
+    delete_stride: usize,
+    substitute_stride: usize,
+    transpose_stride: usize,
+    order: Vec<usize>,
+    r: Vec<B>,
+    prev: Vec<B>,
+    t: Vec<B>,
+    match_bit: B,
+}
+
+impl<B: BitVector> Budgeted<B> {
+    pub(crate) fn new(pattern_length: usize, budget: EditCounts) -> Budgeted<B> {
+        let mut states = Vec::new();
+        for insertions in 0..=budget.insertions {
+            for deletions in 0..=budget.deletions {
+                for substitutions in 0..=budget.substitutions {
+                    for transpositions in 0..=budget.transpositions {
+                        states.push(EditCounts {
+                            insertions,
+                            deletions,
+                            substitutions,
+                            transpositions,
+                        });
+                    }
+                }
+            }
+        }
+        let transpose_stride = 1;
+        let substitute_stride = (budget.transpositions + 1) * transpose_stride;
+        let delete_stride = (budget.substitutions + 1) * substitute_stride;
+        let insert_stride = (budget.deletions + 1) * delete_stride;
+        let mut order = (0..states.len()).collect::<Vec<_>>();
+        order.sort_by_key(|&x| states[x].total());
+
+        Budgeted {
+            r: vec![!B::zero(); states.len()],
+            prev: vec![!B::zero(); states.len()],
+            t: vec![!B::zero(); states.len()],
+            states,
+            insert_stride,
+            delete_stride,
+            substitute_stride,
+            transpose_stride,
+            order,
+            match_bit: B::one() << pattern_length,
+        }
+    }
+}
+
+impl<B: BitVector> Step<B> for Budgeted<B> {
+    type Output = EditCounts;
+
+    #[inline]
+    fn step(&mut self, mask: B, can_start: bool) -> Option<EditCounts> {
+        let (r, prev, t) = (&mut self.r, &mut self.prev, &mut self.t);
+        prev.copy_from_slice(r);
+        if can_start {
+            // Like the other searches, but it's the number of deletions that
+            // says how much of the pattern can be skipped at the start.
+            for (p, c) in prev.iter_mut().zip(&self.states) {
+                *p = *p & (!B::one() << c.deletions);
+            }
+        }
+        for (x, c) in self.states.iter().enumerate() {
+            let mut next = (prev[x] | mask) << 1;
+            if c.deletions > 0 {
+                next = next & (r[x - self.delete_stride] << 1);
+            }
+            if c.substitutions > 0 {
+                next = next & (prev[x - self.substitute_stride] << 1);
+            }
+            if c.transpositions > 0 {
+                next = next & ((t[x] | (mask << 1)) << 1);
+                t[x] = transposition_start(prev[x - self.transpose_stride], mask, 0);
+            }
+            next = next | B::one();
+            if c.insertions > 0 {
+                next = next & prev[x - self.insert_stride];
+            }
+            r[x] = next;
+        }
+        let match_bit = self.match_bit;
+        self.order
+            .iter()
+            .find(|&&x| B::zero() == (r[x] & match_bit))
+            .map(|&x| self.states[x])
+    }
+}
+
+/// Caps a budget to what a pattern of the passed length can use, failing if
+/// that's still too many combinations of edit counts.
+pub(crate) fn clamp_budget(budget: EditCounts, pattern_length: usize) -> Result<EditCounts, Error> {
+    // A pattern can only lose so many characters.
+    let budget = EditCounts {
+        deletions: cmp::min(budget.deletions, pattern_length),
+        substitutions: cmp::min(budget.substitutions, pattern_length),
+        transpositions: cmp::min(budget.transpositions, pattern_length / 2),
+        ..budget
+    };
+    // Check before building anything, since a big enough budget would never
+    // fit in memory.
+    let states = [
+        budget.insertions,
+        budget.deletions,
+        budget.substitutions,
+        budget.transpositions,
+    ]
+    .iter()
+    .fold(1usize, |states, &count| {
+        states.saturating_mul(count.saturating_add(1))
+    });
+    if states > MAX_BUDGET_STATES {
+        return Err(Error::BudgetTooLarge {
+            states,
+            max: MAX_BUDGET_STATES,
+        });
+    }
+    Ok(budget)
+}
@@ -0,0 +1,259 @@
+use alloc::borrow::Cow;
+use alloc::vec::Vec;
+
+use crate::bounds::Bounds;
+use crate::class::{self, CharClass};
+use crate::masks::CharMasks;
+use crate::{
+    check_length, length_is_valid, BitVector, Boundary, Engine, Error, Masks, Pattern, Wide,
+};
+
+/// Compiles a `Pattern` with non-default options.
+///
+/// ```
+/// use bitap::{PatternBuilder};
+/// let pattern = PatternBuilder::new("WORLD").case_insensitive(true).build()?;
+/// assert_eq!(pattern.find("hello World").next().map(|m| m.start), Some(6));
+/// # Ok::<(), bitap::Error>(())
+/// ```
+#[derive(Clone, Debug)]
+pub struct PatternBuilder<'a> {
+    source: Source<'a>,
+    case_insensitive: bool,
+    syntax: bool,
+    text_wildcards: Vec<char>,
+    engine: Engine,
+    bounds: Bounds,
+}
+
+#[derive(Clone, Debug)]
+enum Source<'a> {
+    Str(&'a str),
+    Classes(&'a [CharClass]),
+}
+
+impl<'a> PatternBuilder<'a> {
+    /// Returns a builder for the passed pattern string, with all of the
+    /// options set to their defaults.
+    pub fn new(pattern: &'a str) -> PatternBuilder<'a> {
+        PatternBuilder {
+            source: Source::Str(pattern),
+            case_insensitive: false,
+            syntax: false,
+            text_wildcards: Vec::new(),
+            engine: Engine::Auto,
+            bounds: Bounds::default(),
+        }
+    }
+
+    /// Returns a builder for a pattern made up of the passed character
+    /// classes, one per position.
+    ///
+    /// ```
+    /// use bitap::{CharClass, PatternBuilder};
+    /// let classes = [
+    ///     CharClass::Set(vec!['0'..='9']),
+    ///     CharClass::Char('-'),
+    ///     CharClass::NotSet(vec!['x'..='x']),
+    ///     CharClass::Any,
+    /// ];
+    /// let pattern = PatternBuilder::from_classes(&classes).build()?;
+    /// assert_eq!(pattern.find("x-yz 7-yz").next().map(|m| m.start), Some(5));
+    /// # Ok::<(), bitap::Error>(())
+    /// ```
+    pub fn from_classes(classes: &'a [CharClass]) -> PatternBuilder<'a> {
+        PatternBuilder {
+            source: Source::Classes(classes),
+            case_insensitive: false,
+            syntax: false,
+            text_wildcards: Vec::new(),
+            engine: Engine::Auto,
+            bounds: Bounds::default(),
+        }
+    }
+
+    /// Sets whether matching ignores case. Defaults to false.
+    ///
+    /// Each pattern character matches every character that is equal to it
+    /// under Unicode simple case folding, so "k" matches "K" and the Kelvin
+    /// sign, and "σ" matches "Σ" and "ς". Multi-character mappings like "ß"
+    /// and "SS" are not considered equal. This costs nothing at search time,
+    /// and the text is searched as-is, so indexes still refer to it.
+    pub fn case_insensitive(mut self, yes: bool) -> PatternBuilder<'a> {
+        self.case_insensitive = yes;
+        self
+    }
+
+    /// Sets whether the pattern string is parsed for character classes and
+    /// wildcards. Defaults to false, so every character is taken literally.
+    /// Has no effect on patterns built with `from_classes`.
+    ///
+    /// - `?` matches any character.
+    /// - `[abc]` matches any of the characters inside the brackets, and
+    ///   `[a-z]` any character in the range. `[^abc]` or `[!abc]` match any
+    ///   character that isn't inside the brackets. A `]` right after the
+    ///   opening bracket and a `-` at either end are taken literally.
+    /// - `{n}` repeats whatever came right before it `n` times in total.
+    /// - `\` escapes the character after it, so `\?` matches a question mark.
+    ///
+    /// Each class takes up a single position of the pattern, so all of this
+    /// is compiled into the masks and searching is exactly as fast as it is
+    /// for plain patterns.
+    ///
+    /// ```
+    /// use bitap::{PatternBuilder, TextMatch};
+    /// let pattern = PatternBuilder::new("[0-9]{3}-item").syntax(true).build()?;
+    /// assert_eq!(pattern.len(), 8);
+    /// assert_eq!(pattern.find("see 042-item").next().map(|m| m.start), Some(4));
+    /// let pattern = PatternBuilder::new("colo?r").syntax(true).build()?;
+    /// let m = pattern.lev("a color", 1).next();
+    /// assert_eq!(m, Some(TextMatch{ distance: 1, end: 6, byte_end: 7 }));
+    /// # Ok::<(), bitap::Error>(())
+    /// ```
+    pub fn syntax(mut self, yes: bool) -> PatternBuilder<'a> {
+        self.syntax = yes;
+        self
+    }
+
+    /// Adds characters that, when they show up in the _text_, match any
+    /// position of the pattern for free. Defaults to none.
+    ///
+    /// This is useful for placeholders that stand in for characters that
+    /// couldn't be read, like the replacement character that lossy UTF-8
+    /// decoding and some OCR software produce. The characters are taken
+    /// exactly as they are, even when matching is case insensitive.
+    ///
+    /// ```
+    /// use bitap::PatternBuilder;
+    /// let pattern = PatternBuilder::new("hello")
+    ///     .text_wildcards(vec!['\u{FFFD}'])
+    ///     .build()?;
+    /// assert_eq!(pattern.find("h\u{FFFD}llo w\u{FFFD}rld").next().map(|m| m.start), Some(0));
+    /// # Ok::<(), bitap::Error>(())
+    /// ```
+    pub fn text_wildcards<I: IntoIterator<Item = char>>(mut self, chars: I) -> PatternBuilder<'a> {
+        self.text_wildcards.extend(chars);
+        self
+    }
+
+    /// Sets the engine that `Pattern::lev` and `Pattern::osa` search with.
+    /// Defaults to `Engine::Auto`, which picks the fastest one for each
+    /// search, so this is mostly useful for comparing them. `Engine::Myers`
+    /// can't be used with `match_start(Boundary::Word)`.
+    ///
+    /// ```
+    /// use bitap::{Engine, PatternBuilder, TextMatch};
+    /// let pattern = PatternBuilder::new("wxrld").engine(Engine::Myers).build()?;
+    /// assert_eq!(pattern.engine_for(1), Engine::Myers);
+    /// let m = pattern.lev("hello world", 1).next();
+    /// assert_eq!(m, Some(TextMatch{ distance: 1, end: 10, byte_end: 11 }));
+    /// # Ok::<(), bitap::Error>(())
+    /// ```
+    pub fn engine(mut self, engine: Engine) -> PatternBuilder<'a> {
+        self.engine = engine;
+        self
+    }
+
+    /// Sets where matches found by `Pattern`'s searches are allowed to start.
+    /// Defaults to `Boundary::Anywhere`.
+    ///
+    /// This is checked as part of the search rather than by filtering the
+    /// matches afterwards, so a match is only found if there's an alignment
+    /// within the max distance that starts in the right place, and its
+    /// distance is the lowest out of those.
+    ///
+    /// A restricted start means a match can need more edits than there are
+    /// characters in the pattern, up to the length of the text since the
+    /// last place it can start, and the search gets slower as it needs them.
+    /// With `Boundary::Text` that can be the whole text, so `Engine::Auto`
+    /// uses `Engine::Myers` for any max distance past three, which costs the
+    /// same whatever it is. `Engine::Myers` can't handle `Boundary::Word`, so
+    /// building a pattern that asks for both fails with
+    /// `Error::UnsupportedBounds`.
+    ///
+    /// ```
+    /// use bitap::{Boundary, PatternBuilder};
+    /// let pattern = PatternBuilder::new("cat")
+    ///     .match_start(Boundary::Word)
+    ///     .match_end(Boundary::Word)
+    ///     .build()?;
+    /// assert_eq!(pattern.lev("concatenate", 0).next(), None);
+    /// assert_eq!(pattern.lev("a cat!", 0).next().map(|m| m.end), Some(4));
+    /// # Ok::<(), bitap::Error>(())
+    /// ```
+    pub fn match_start(mut self, boundary: Boundary) -> PatternBuilder<'a> {
+        self.bounds.start = boundary;
+        self
+    }
+
+    /// Sets where matches found by `Pattern`'s searches are allowed to end.
+    /// Defaults to `Boundary::Anywhere`. See `match_start`.
+    pub fn match_end(mut self, boundary: Boundary) -> PatternBuilder<'a> {
+        self.bounds.end = boundary;
+        self
+    }
+
+    /// Sets which characters are part of a word for `Boundary::Word`.
+    /// Defaults to `char::is_alphanumeric`.
+    ///
+    /// ```
+    /// use bitap::{Boundary, PatternBuilder};
+    /// let pattern = PatternBuilder::new("id")
+    ///     .match_start(Boundary::Word)
+    ///     .word_chars(|c| c.is_alphanumeric() || c == '_')
+    ///     .build()?;
+    /// assert_eq!(pattern.lev("user_id", 0).next(), None);
+    /// # Ok::<(), bitap::Error>(())
+    /// ```
+    pub fn word_chars(mut self, is_word: fn(char) -> bool) -> PatternBuilder<'a> {
+        self.bounds.is_word = is_word;
+        self
+    }
+
+    /// Compiles the pattern. Will fail if the pattern is empty, longer than
+    /// `MAX_PATTERN_LENGTH`, isn't valid syntax, or forces `Engine::Myers`
+    /// on matches that have to start at the edge of a word.
+    pub fn build(&self) -> Result<Pattern, Error> {
+        self.build_with_word()
+    }
+
+    /// Like `build`, but compiles patterns that fit into a single word of
+    /// type `W` instead of a `usize`. Longer patterns still use `Wide` masks,
+    /// up to `MAX_PATTERN_LENGTH`.
+    ///
+    /// ```
+    /// use bitap::{Pattern, PatternBuilder};
+    /// let pattern: Pattern<u128> = PatternBuilder::new(&"a".repeat(100)).build_with_word()?;
+    /// assert_eq!(pattern.len(), 100);
+    /// # Ok::<(), bitap::Error>(())
+    /// ```
+    pub fn build_with_word<W: BitVector>(&self) -> Result<Pattern<W>, Error> {
+        let classes = match self.source {
+            Source::Str(pattern) if self.syntax => Cow::Owned(class::parse(pattern)?),
+            Source::Str(pattern) => Cow::Owned(pattern.chars().map(CharClass::Char).collect()),
+            Source::Classes(classes) => Cow::Borrowed(classes),
+        };
+        let length = classes.len();
+        check_length::<Wide<4>>(length)?;
+        if self.engine == Engine::Myers && self.bounds.start == Boundary::Word {
+            return Err(Error::UnsupportedBounds);
+        }
+        let masks = if length_is_valid::<W>(length) {
+            Masks::Word(self.compile_masks(&classes))
+        } else if length_is_valid::<Wide<2>>(length) {
+            Masks::Wide2(self.compile_masks(&classes))
+        } else {
+            Masks::Wide4(self.compile_masks(&classes))
+        };
+        Ok(Pattern {
+            length,
+            masks,
+            engine: self.engine,
+            bounds: self.bounds,
+        })
+    }
+
+    fn compile_masks<B: BitVector>(&self, classes: &[CharClass]) -> CharMasks<B> {
+        CharMasks::new(classes, self.case_insensitive, &self.text_wildcards)
+    }
+}
@@ -0,0 +1,163 @@
+use alloc::boxed::Box;
+
+use crate::{
+    check_length, find, hamming, hamming_static, length_is_valid, levenshtein, levenshtein_static,
+    optimal_string_alignment, optimal_string_alignment_static, BitVector, Dispatch, Error, Match,
+    StaticMaxDistance, Wide,
+};
+
+/// A compiled pattern of bytes that can be used to search byte slices.
+///
+/// This is the same as `Pattern`, but works on raw bytes rather than `char`s,
+/// so it can search binary data or text that isn't valid UTF-8. All of the
+/// indexes it returns are byte offsets.
+pub struct BytePattern {
+    length: usize,
+    masks: ByteMasks,
+}
+
+/// A mask for every possible byte, stored using the narrowest bit vector
+/// that fits the pattern.
+enum ByteMasks {
+    Word(Box<[usize; 256]>),
+    Wide2(Box<[Wide<2>; 256]>),
+    Wide4(Box<[Wide<4>; 256]>),
+}
+
+impl BytePattern {
+    /// Compiles and returns a new pattern from the passed bytes. Will fail if
+    /// the passed pattern is empty or longer than `MAX_PATTERN_LENGTH`.
+    pub fn new(pattern: &[u8]) -> Result<BytePattern, Error> {
+        let length = pattern.len();
+        check_length::<Wide<4>>(length)?;
+        let masks = if length_is_valid::<usize>(length) {
+            ByteMasks::Word(compile_masks(pattern))
+        } else if length_is_valid::<Wide<2>>(length) {
+            ByteMasks::Wide2(compile_masks(pattern))
+        } else {
+            ByteMasks::Wide4(compile_masks(pattern))
+        };
+        Ok(BytePattern { length, masks })
+    }
+
+    /// Returns the length of the pattern in bytes.
+    // Patterns can never be empty, so an `is_empty` method would be useless.
+    #[allow(clippy::len_without_is_empty)]
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.length
+    }
+
+    /// Returns an iterator of byte offsets where the pattern can be found
+    /// within the passed text.
+    ///
+    /// ```
+    /// use bitap::{BytePattern};
+    /// let pattern = BytePattern::new(b"world")?;
+    /// assert_eq!(pattern.find(b"hello \xffworld").next(), Some(7));
+    /// # Ok::<(), bitap::Error>(())
+    /// ```
+    pub fn find<'a>(&'a self, text: &'a [u8]) -> impl Iterator<Item = usize> + 'a {
+        dispatch!(ByteMasks, &self.masks, masks => {
+            find(mask_iter(masks, text), self.len()).unwrap()
+        })
+    }
+
+    /// Returns an iterator of matches where the pattern matched the passed
+    /// text within a levenshtein distance of `max_distance`.
+    ///
+    /// ```
+    /// use bitap::{BytePattern,Match};
+    /// let pattern = BytePattern::new(b"wxrld")?;
+    /// let m = pattern.lev(b"hello \xffworld", 1).next();
+    /// assert_eq!(m, Some(Match{ distance: 1, end: 11 }));
+    /// # Ok::<(), bitap::Error>(())
+    /// ```
+    pub fn lev<'a>(
+        &'a self,
+        text: &'a [u8],
+        max_distance: usize,
+    ) -> impl Iterator<Item = Match> + 'a {
+        dispatch!(ByteMasks, &self.masks, masks => {
+            levenshtein(mask_iter(masks, text), self.len(), max_distance).unwrap()
+        })
+    }
+
+    /// Returns an iterator of matches where the pattern matched the passed
+    /// text within an optimal string alignment distance of `max_distance`.
+    pub fn osa<'a>(
+        &'a self,
+        text: &'a [u8],
+        max_distance: usize,
+    ) -> impl Iterator<Item = Match> + 'a {
+        dispatch!(ByteMasks, &self.masks, masks => {
+            optimal_string_alignment(mask_iter(masks, text), self.len(), max_distance).unwrap()
+        })
+    }
+
+    /// Returns an iterator of matches where the pattern matched the passed
+    /// text within a hamming distance of `max_distance`.
+    pub fn hamming<'a>(
+        &'a self,
+        text: &'a [u8],
+        max_distance: usize,
+    ) -> impl Iterator<Item = Match> + 'a {
+        dispatch!(ByteMasks, &self.masks, masks => {
+            hamming(mask_iter(masks, text), self.len(), max_distance).unwrap()
+        })
+    }
+
+    /// The same as lev, but optimized for a `max_distance` of 1-2.
+    pub fn lev_static<'a>(
+        &'a self,
+        text: &'a [u8],
+        max_distance: StaticMaxDistance,
+    ) -> impl Iterator<Item = Match> + 'a {
+        dispatch!(ByteMasks, &self.masks, masks => {
+            levenshtein_static(mask_iter(masks, text), self.len(), max_distance).unwrap()
+        })
+    }
+
+    /// The same as osa, but optimized for a `max_distance` of 1-2.
+    pub fn osa_static<'a>(
+        &'a self,
+        text: &'a [u8],
+        max_distance: StaticMaxDistance,
+    ) -> impl Iterator<Item = Match> + 'a {
+        dispatch!(ByteMasks, &self.masks, masks => {
+            optimal_string_alignment_static(mask_iter(masks, text), self.len(), max_distance)
+                .unwrap()
+        })
+    }
+
+    /// The same as hamming, but optimized for a `max_distance` of 1-2.
+    pub fn hamming_static<'a>(
+        &'a self,
+        text: &'a [u8],
+        max_distance: StaticMaxDistance,
+    ) -> impl Iterator<Item = Match> + 'a {
+        dispatch!(ByteMasks, &self.masks, masks => {
+            hamming_static(mask_iter(masks, text), self.len(), max_distance).unwrap()
+        })
+    }
+}
+
+/// Creates the mask for every byte. See `CharMasks::new` for what a mask is;
+/// the only difference is that every byte gets one, so looking them up is
+/// just indexing into the table.
+fn compile_masks<B: BitVector>(pattern: &[u8]) -> Box<[B; 256]> {
+    let mut masks = Box::new([!B::zero(); 256]);
+    for (i, &b) in pattern.iter().enumerate() {
+        let mask = &mut masks[b as usize];
+        *mask = *mask & !(B::one() << i);
+    }
+    masks
+}
+
+#[inline]
+fn mask_iter<'a, B: BitVector>(
+    masks: &'a [B; 256],
+    text: &'a [u8],
+) -> impl Iterator<Item = B> + 'a {
+    text.iter().map(move |&b| masks[b as usize])
+}
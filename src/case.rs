@@ -0,0 +1,164 @@
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// Characters that are equal to another character ignoring case, but that
+/// can't be found by converting that character to upper and lower case, along
+/// with the character that they fold to.
+///
+/// These are things like the Kelvin sign, which lowercases to a plain "k",
+/// while "k" uppercases to a plain "K". Derived from the simple case folding
+/// rules in Unicode's `CaseFolding.txt`. Sorted so that it can be searched.
+static FOLD_EXCEPTIONS: &[(char, char)] = &[
+    ('\u{00B5}', '\u{03BC}'), // µ -> μ
+    ('\u{017F}', '\u{0073}'), // ſ -> s
+    ('\u{01C5}', '\u{01C6}'), // ǅ -> ǆ
+    ('\u{01C8}', '\u{01C9}'), // ǈ -> ǉ
+    ('\u{01CB}', '\u{01CC}'), // ǋ -> ǌ
+    ('\u{01F2}', '\u{01F3}'), // ǲ -> ǳ
+    ('\u{0345}', '\u{03B9}'), // ͅ -> ι
+    ('\u{03C2}', '\u{03C3}'), // ς -> σ
+    ('\u{03D0}', '\u{03B2}'), // ϐ -> β
+    ('\u{03D1}', '\u{03B8}'), // ϑ -> θ
+    ('\u{03D5}', '\u{03C6}'), // ϕ -> φ
+    ('\u{03D6}', '\u{03C0}'), // ϖ -> π
+    ('\u{03F0}', '\u{03BA}'), // ϰ -> κ
+    ('\u{03F1}', '\u{03C1}'), // ϱ -> ρ
+    ('\u{03F4}', '\u{03B8}'), // ϴ -> θ
+    ('\u{03F5}', '\u{03B5}'), // ϵ -> ε
+    ('\u{1C80}', '\u{0432}'), // ᲀ -> в
+    ('\u{1C81}', '\u{0434}'), // ᲁ -> д
+    ('\u{1C82}', '\u{043E}'), // ᲂ -> о
+    ('\u{1C83}', '\u{0441}'), // ᲃ -> с
+    ('\u{1C84}', '\u{0442}'), // ᲄ -> т
+    ('\u{1C85}', '\u{0442}'), // ᲅ -> т
+    ('\u{1C86}', '\u{044A}'), // ᲆ -> ъ
+    ('\u{1C87}', '\u{0463}'), // ᲇ -> ѣ
+    ('\u{1C88}', '\u{A64B}'), // ᲈ -> ꙋ
+    ('\u{1E9B}', '\u{1E61}'), // ẛ -> ṡ
+    ('\u{1E9E}', '\u{00DF}'), // ẞ -> ß
+    ('\u{1F88}', '\u{1F80}'), // ᾈ -> ᾀ
+    ('\u{1F89}', '\u{1F81}'), // ᾉ -> ᾁ
+    ('\u{1F8A}', '\u{1F82}'), // ᾊ -> ᾂ
+    ('\u{1F8B}', '\u{1F83}'), // ᾋ -> ᾃ
+    ('\u{1F8C}', '\u{1F84}'), // ᾌ -> ᾄ
+    ('\u{1F8D}', '\u{1F85}'), // ᾍ -> ᾅ
+    ('\u{1F8E}', '\u{1F86}'), // ᾎ -> ᾆ
+    ('\u{1F8F}', '\u{1F87}'), // ᾏ -> ᾇ
+    ('\u{1F98}', '\u{1F90}'), // ᾘ -> ᾐ
+    ('\u{1F99}', '\u{1F91}'), // ᾙ -> ᾑ
+    ('\u{1F9A}', '\u{1F92}'), // ᾚ -> ᾒ
+    ('\u{1F9B}', '\u{1F93}'), // ᾛ -> ᾓ
+    ('\u{1F9C}', '\u{1F94}'), // ᾜ -> ᾔ
+    ('\u{1F9D}', '\u{1F95}'), // ᾝ -> ᾕ
+    ('\u{1F9E}', '\u{1F96}'), // ᾞ -> ᾖ
+    ('\u{1F9F}', '\u{1F97}'), // ᾟ -> ᾗ
+    ('\u{1FA8}', '\u{1FA0}'), // ᾨ -> ᾠ
+    ('\u{1FA9}', '\u{1FA1}'), // ᾩ -> ᾡ
+    ('\u{1FAA}', '\u{1FA2}'), // ᾪ -> ᾢ
+    ('\u{1FAB}', '\u{1FA3}'), // ᾫ -> ᾣ
+    ('\u{1FAC}', '\u{1FA4}'), // ᾬ -> ᾤ
+    ('\u{1FAD}', '\u{1FA5}'), // ᾭ -> ᾥ
+    ('\u{1FAE}', '\u{1FA6}'), // ᾮ -> ᾦ
+    ('\u{1FAF}', '\u{1FA7}'), // ᾯ -> ᾧ
+    ('\u{1FBC}', '\u{1FB3}'), // ᾼ -> ᾳ
+    ('\u{1FBE}', '\u{03B9}'), // ι -> ι
+    ('\u{1FCC}', '\u{1FC3}'), // ῌ -> ῃ
+    ('\u{1FFC}', '\u{1FF3}'), // ῼ -> ῳ
+    ('\u{2126}', '\u{03C9}'), // Ω -> ω
+    ('\u{212A}', '\u{006B}'), // K -> k
+    ('\u{212B}', '\u{00E5}'), // Å -> å
+];
+
+/// Ranges that hold every character with a case variant other than itself,
+/// so that big classes only need to look at the few blocks that have any
+/// case at all. Some ranges have a few uncased characters in them too, which
+/// is harmless. Sorted and disjoint.
+static CASED_RANGES: &[(char, char)] = &[
+    ('\u{0041}', '\u{007A}'),
+    ('\u{00B5}', '\u{029E}'),
+    ('\u{0345}', '\u{0345}'),
+    ('\u{0370}', '\u{0586}'),
+    ('\u{10A0}', '\u{10FF}'),
+    ('\u{13A0}', '\u{13FD}'),
+    ('\u{1C80}', '\u{1CBF}'),
+    ('\u{1D79}', '\u{1D8E}'),
+    ('\u{1E00}', '\u{1FFC}'),
+    ('\u{2126}', '\u{2184}'),
+    ('\u{24B6}', '\u{24E9}'),
+    ('\u{2C00}', '\u{2D2D}'),
+    ('\u{A640}', '\u{A69B}'),
+    ('\u{A722}', '\u{A7F6}'),
+    ('\u{AB53}', '\u{ABBF}'),
+    ('\u{FF21}', '\u{FF5A}'),
+    ('\u{10400}', '\u{1044F}'),
+    ('\u{104B0}', '\u{104FB}'),
+    ('\u{10570}', '\u{105BC}'),
+    ('\u{10C80}', '\u{10CF2}'),
+    ('\u{10D50}', '\u{10D85}'),
+    ('\u{118A0}', '\u{118DF}'),
+    ('\u{16E40}', '\u{16E7F}'),
+    ('\u{16EA0}', '\u{16ED3}'),
+    ('\u{1E900}', '\u{1E943}'),
+];
+
+/// Returns the single character that `chars` yields, or `c` if it yields
+/// more than one. Multi-character mappings (like "ß" uppercasing to "SS")
+/// aren't simple case mappings, so they're ignored.
+#[inline]
+fn single<I: Iterator<Item = char>>(mut chars: I, c: char) -> char {
+    match (chars.next(), chars.next()) {
+        (Some(mapped), None) => mapped,
+        _ => c,
+    }
+}
+
+#[inline]
+fn lower(c: char) -> char {
+    single(c.to_lowercase(), c)
+}
+
+#[inline]
+fn upper(c: char) -> char {
+    single(c.to_uppercase(), c)
+}
+
+/// Returns the simple case folding of a character. Two characters are equal
+/// ignoring case exactly when they fold to the same character.
+pub(crate) fn fold(c: char) -> char {
+    match FOLD_EXCEPTIONS.binary_search_by_key(&c, |&(c, _)| c) {
+        Ok(i) => FOLD_EXCEPTIONS[i].1,
+        Err(_) => lower(c),
+    }
+}
+
+/// Returns every character that is equal to `c` ignoring case, including `c`
+/// itself.
+pub(crate) fn case_variants(c: char) -> Vec<char> {
+    let folded = fold(c);
+    let mut variants = vec![c];
+    for &mapped in &[lower(c), upper(c)] {
+        variants.extend_from_slice(&[mapped, lower(mapped), upper(mapped)]);
+    }
+    variants.extend(
+        FOLD_EXCEPTIONS
+            .iter()
+            .filter(|&&(_, f)| f == folded)
+            .map(|&(c, _)| c),
+    );
+    // Round tripping can wander off into a different case class, like "ı"
+    // uppercasing to "I" and then lowercasing to "i".
+    variants.retain(|&v| fold(v) == folded);
+    variants.sort();
+    variants.dedup();
+    variants
+}
+
+/// Returns the characters between `start` and `end` that might have case
+/// variants. Everything else in the range is only equal to itself ignoring
+/// case.
+pub(crate) fn cased_between(start: char, end: char) -> impl Iterator<Item = char> {
+    CASED_RANGES
+        .iter()
+        .filter(move |&&(first, last)| first <= end && start <= last)
+        .flat_map(move |&(first, last)| first.max(start)..=last.min(end))
+}
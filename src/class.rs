@@ -0,0 +1,143 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::ops::RangeInclusive;
+
+use crate::case::{case_variants, cased_between};
+use crate::{Error, MAX_PATTERN_LENGTH};
+
+/// The set of characters that a single position of a pattern matches.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum CharClass {
+    /// Matches exactly this character.
+    Char(char),
+    /// Matches any character in one of the ranges.
+    Set(Vec<RangeInclusive<char>>),
+    /// Matches any character that isn't in one of the ranges.
+    NotSet(Vec<RangeInclusive<char>>),
+    /// Matches any character at all.
+    Any,
+}
+
+impl CharClass {
+    /// Returns the ranges of code points in the class, sorted and merged, and
+    /// whether the class is everything _but_ those ranges.
+    pub(crate) fn ranges(&self, case_insensitive: bool) -> (Vec<(u32, u32)>, bool) {
+        let (ranges, negated): (&[RangeInclusive<char>], bool) = match self {
+            CharClass::Char(c) => return (char_ranges(&[*c..=*c], case_insensitive), false),
+            CharClass::Set(ranges) => (ranges, false),
+            CharClass::NotSet(ranges) => (ranges, true),
+            CharClass::Any => (&[], true),
+        };
+        (char_ranges(ranges, case_insensitive), negated)
+    }
+}
+
+fn char_ranges(ranges: &[RangeInclusive<char>], case_insensitive: bool) -> Vec<(u32, u32)> {
+    let mut out = ranges
+        .iter()
+        .filter(|r| r.start() <= r.end())
+        .map(|r| (*r.start() as u32, *r.end() as u32))
+        .collect::<Vec<_>>();
+    if case_insensitive {
+        // Every case variant of every character in the class is in the
+        // class too. Only the cased blocks need looking at, which keeps huge
+        // ranges cheap.
+        for r in ranges {
+            for c in cased_between(*r.start(), *r.end()) {
+                out.extend(case_variants(c).into_iter().map(|v| (v as u32, v as u32)));
+            }
+        }
+    }
+    out.sort();
+    let mut merged: Vec<(u32, u32)> = Vec::with_capacity(out.len());
+    for (start, end) in out {
+        match merged.last_mut() {
+            Some(last) if start <= last.1.saturating_add(1) => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+    merged
+}
+
+/// Parses a pattern string into classes, using the syntax described in
+/// `PatternBuilder::syntax`. Errors point at the character that starts the
+/// invalid escape, set, or repetition.
+pub(crate) fn parse(pattern: &str) -> Result<Vec<CharClass>, Error> {
+    let mut classes = Vec::new();
+    let mut chars = pattern.chars().enumerate().peekable();
+    while let Some((position, c)) = chars.next() {
+        let invalid = move || Error::InvalidSyntax { position };
+        let class = match c {
+            '\\' => CharClass::Char(chars.next().map(|(_, c)| c).ok_or_else(invalid)?),
+            '?' => CharClass::Any,
+            '[' => {
+                let negated = match chars.peek() {
+                    Some((_, '^')) | Some((_, '!')) => {
+                        chars.next();
+                        true
+                    }
+                    _ => false,
+                };
+                let mut ranges = Vec::new();
+                let mut first = true;
+                loop {
+                    let start = match chars.next().map(|(_, c)| c).ok_or_else(invalid)? {
+                        ']' if !first => break,
+                        '\\' => chars.next().map(|(_, c)| c).ok_or_else(invalid)?,
+                        c => c,
+                    };
+                    first = false;
+                    let mut end = start;
+                    if chars.peek().map(|&(_, c)| c) == Some('-') {
+                        chars.next();
+                        end = match chars.next().map(|(_, c)| c).ok_or_else(invalid)? {
+                            // A trailing dash is literal, as in "[a-]".
+                            ']' => {
+                                ranges.push(start..=start);
+                                ranges.push('-'..='-');
+                                break;
+                            }
+                            '\\' => chars.next().map(|(_, c)| c).ok_or_else(invalid)?,
+                            c => c,
+                        };
+                        if end < start {
+                            return Err(invalid());
+                        }
+                    }
+                    ranges.push(start..=end);
+                }
+                if negated {
+                    CharClass::NotSet(ranges)
+                } else {
+                    CharClass::Set(ranges)
+                }
+            }
+            '{' => {
+                let prev = classes.pop().ok_or_else(invalid)?;
+                let mut count = String::new();
+                loop {
+                    match chars.next().map(|(_, c)| c).ok_or_else(invalid)? {
+                        '}' => break,
+                        c if c.is_ascii_digit() => count.push(c),
+                        _ => return Err(invalid()),
+                    }
+                }
+                let count: usize = count.parse().map_err(|_| invalid())?;
+                // Check the length before repeating anything, since a huge
+                // count would never fit in memory anyway.
+                let len = classes.len().checked_add(count).ok_or_else(invalid)?;
+                if len > MAX_PATTERN_LENGTH {
+                    return Err(Error::PatternTooLong {
+                        len,
+                        max: MAX_PATTERN_LENGTH,
+                    });
+                }
+                classes.resize(len, prev);
+                continue;
+            }
+            c => CharClass::Char(c),
+        };
+        classes.push(class);
+    }
+    Ok(classes)
+}
@@ -0,0 +1,287 @@
+use alloc::vec;
+use alloc::vec::Vec;
+use core::{cmp, mem};
+
+use crate::myers::Column;
+use crate::step::transposition_start;
+use crate::{length_is_valid, BitVector, Wide};
+
+/// Returns the levenshtein distance between two whole strings.
+///
+/// This is `myers_levenshtein` with both ends of the match pinned to the ends
+/// of the text, using the shorter string as the pattern. If both strings are
+/// longer than `MAX_PATTERN_LENGTH`, it falls back to filling in the usual
+/// table of distances between prefixes, which takes time proportional to the
+/// product of their lengths. Use `within_distance` if you only need to know
+/// whether the distance is small, since it can give up as soon as it isn't.
+///
+/// ```
+/// use bitap::levenshtein_distance;
+/// assert_eq!(levenshtein_distance("kitten", "sitting"), 3);
+/// assert_eq!(levenshtein_distance("", "abc"), 3);
+/// ```
+pub fn levenshtein_distance(a: &str, b: &str) -> usize {
+    unbounded_distance(a, b, false)
+}
+
+/// Returns the optimal string alignment distance between two whole
+/// strings. See `levenshtein_distance`.
+///
+/// ```
+/// use bitap::osa_distance;
+/// assert_eq!(osa_distance("abcd", "acbd"), 1);
+/// assert_eq!(osa_distance("ca", "abc"), 3);
+/// ```
+pub fn osa_distance(a: &str, b: &str) -> usize {
+    unbounded_distance(a, b, true)
+}
+
+/// Returns whether two whole strings are within a levenshtein distance of
+/// `max_distance` of each other.
+///
+/// The search stops as soon as no prefix of the shorter string is within
+/// `max_distance` of what's been seen of the longer one, and strings whose
+/// lengths differ by more than `max_distance` aren't searched at all, so
+/// this is much faster than `levenshtein_distance` for strings that aren't
+/// close. Strings too long for a bit vector only fill in the part of the
+/// table within `max_distance` of the diagonal.
+///
+/// ```
+/// use bitap::within_distance;
+/// assert!(within_distance("hello", "hallo", 1));
+/// assert!(!within_distance("hello", "world", 3));
+/// ```
+pub fn within_distance(a: &str, b: &str, max_distance: usize) -> bool {
+    bounded_distance(a, b, max_distance, false).is_some()
+}
+
+/// Finds the distance with a single pass of Myers' algorithm, which costs
+/// the same however far apart the strings are.
+fn unbounded_distance(a: &str, b: &str, allow_transpositions: bool) -> usize {
+    let (pattern, length, text, text_length) = shorter_first(a, b);
+    if length == 0 {
+        text_length
+    } else if length_is_valid::<usize>(length) {
+        myers::<usize>(pattern, length, text, allow_transpositions)
+    } else if length_is_valid::<Wide<2>>(length) {
+        myers::<Wide<2>>(pattern, length, text, allow_transpositions)
+    } else if length_is_valid::<Wide<4>>(length) {
+        myers::<Wide<4>>(pattern, length, text, allow_transpositions)
+    } else {
+        // Nothing is further apart than the longer string is long, so this
+        // always finds it.
+        table(pattern, text, usize::MAX, allow_transpositions).unwrap_or(text_length)
+    }
+}
+
+/// Returns the distance between two whole strings if it's at most
+/// `max_distance`.
+fn bounded_distance(
+    a: &str,
+    b: &str,
+    max_distance: usize,
+    allow_transpositions: bool,
+) -> Option<usize> {
+    let (pattern, length, text, text_length) = shorter_first(a, b);
+    // Every extra character of the text has to be inserted.
+    if text_length - length > max_distance {
+        None
+    } else if length == 0 {
+        Some(text_length)
+    } else if length_is_valid::<usize>(length) {
+        anchored::<usize>(pattern, length, text, max_distance, allow_transpositions)
+    } else if length_is_valid::<Wide<2>>(length) {
+        anchored::<Wide<2>>(pattern, length, text, max_distance, allow_transpositions)
+    } else if length_is_valid::<Wide<4>>(length) {
+        anchored::<Wide<4>>(pattern, length, text, max_distance, allow_transpositions)
+    } else {
+        table(pattern, text, max_distance, allow_transpositions)
+    }
+}
+
+/// Returns the shorter string and its length, then the longer one and its
+/// length. Both distances are symmetric, so the shorter string can always be
+/// the pattern.
+fn shorter_first<'a>(a: &'a str, b: &'a str) -> (&'a str, usize, &'a str, usize) {
+    let (a_length, b_length) = (a.chars().count(), b.chars().count());
+    if a_length <= b_length {
+        (a, a_length, b, b_length)
+    } else {
+        (b, b_length, a, a_length)
+    }
+}
+
+/// Runs `myers_levenshtein` or `myers_optimal_string_alignment` over the
+/// whole text, with the start of the match pinned to the start of the text,
+/// and returns the distance at the end of it.
+fn myers<B: BitVector>(
+    pattern: &str,
+    length: usize,
+    text: &str,
+    allow_transpositions: bool,
+) -> usize {
+    let masks = char_masks::<B>(pattern);
+    let mut column = Column::<B>::anchored(length);
+    let mut distance = length;
+    let (mut prev_eq, mut prev_d0) = (B::zero(), !B::zero());
+    for c in text.chars() {
+        let eq = match masks.binary_search_by_key(&c, |&(mc, _)| mc) {
+            Ok(found) => !masks[found].1,
+            Err(_) => B::zero(),
+        };
+        let mut d0 = column.diagonal(eq);
+        if allow_transpositions {
+            d0 = d0 | (((!prev_d0 & eq) << 1) & prev_eq);
+        }
+        distance = column.advance(d0);
+        prev_eq = eq;
+        prev_d0 = d0;
+    }
+    distance
+}
+
+/// Runs `levenshtein` or `optimal_string_alignment` over the whole text,
+/// returning the distance if the pattern matched at the end of it.
+///
+/// The only difference is the start of the match. A normal search can start
+/// matching anywhere, so the empty prefix of the pattern always matches, and
+/// every step shifts a zero into the bottom of the state. Here it only
+/// matches at level `j` while no more than `j` characters of the text have
+/// been seen, since each of them had to be inserted.
+fn anchored<B: BitVector>(
+    pattern: &str,
+    length: usize,
+    text: &str,
+    max_distance: usize,
+    allow_transpositions: bool,
+) -> Option<usize> {
+    let masks = char_masks::<B>(pattern);
+    let match_bit = B::one() << length;
+    // Every prefix of the pattern, including the empty one and the whole
+    // thing.
+    let prefixes = !(!B::one() << length);
+    // Deleting the whole pattern is as far as the initial state goes.
+    let mut r: Vec<B> = (0..=max_distance)
+        .map(|j| !B::one() << cmp::min(j, length))
+        .collect();
+    let mut t = vec![!B::zero(); max_distance];
+
+    for (i, c) in text.chars().enumerate() {
+        let mask = match masks.binary_search_by_key(&c, |&(mc, _)| mc) {
+            Ok(found) => masks[found].1,
+            Err(_) => !B::zero(),
+        };
+        let mut prev_parent = r[0];
+        r[0] = ((r[0] | mask) << 1) | B::one();
+        for j in 1..r.len() {
+            let prev = r[j];
+            let current = (prev | mask) << 1;
+            let replace = prev_parent << 1;
+            let delete = r[j - 1] << 1;
+            let insert = prev_parent;
+            r[j] = current & insert & delete & replace;
+            if allow_transpositions {
+                let transpose = (t[j - 1] | (mask << 1)) << 1;
+                r[j] = r[j] & transpose;
+                t[j - 1] = transposition_start(prev_parent, mask, 0);
+            }
+            if i >= j {
+                r[j] = r[j] | B::one();
+            }
+            prev_parent = prev;
+        }
+        // Once no prefix matches within the max distance, none ever will
+        // again.
+        if prefixes == (r[max_distance] & prefixes) {
+            return None;
+        }
+    }
+    r.iter().position(|&rv| B::zero() == (rv & match_bit))
+}
+
+/// Returns the distance between two whole strings if it's at most
+/// `max_distance`, the slow way, for strings too long to fit in a bit
+/// vector.
+///
+/// This fills in the table of distances between every prefix of the pattern
+/// and every prefix of the text, a row for each character of the text.
+/// Prefixes whose lengths differ by more than `max_distance` are at least
+/// that far apart, so only the band of the table around the diagonal is
+/// filled in, and everything outside it is just too far.
+pub(crate) fn table(
+    pattern: &str,
+    text: &str,
+    max_distance: usize,
+    allow_transpositions: bool,
+) -> Option<usize> {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    if pattern.is_empty() {
+        return Some(text.chars().count()).filter(|&d| d <= max_distance);
+    }
+    let too_far = max_distance.saturating_add(1);
+    let mut row = (0..=pattern.len())
+        .map(|j| cmp::min(j, too_far))
+        .collect::<Vec<_>>();
+    // The row before this one, and the one before that for transpositions.
+    let mut above = vec![too_far; row.len()];
+    let mut above_that = vec![too_far; row.len()];
+    let mut prev_c = None;
+
+    for (i, c) in text.chars().enumerate().map(|(i, c)| (i + 1, c)) {
+        let first = cmp::max(i.saturating_sub(max_distance), 1);
+        let last = cmp::min(i.saturating_add(max_distance), pattern.len());
+        // Every prefix of the pattern is too short to be close enough.
+        if first > last {
+            return None;
+        }
+        mem::swap(&mut above_that, &mut above);
+        mem::swap(&mut above, &mut row);
+        // The rows are reused, so the cells either side of the band have to
+        // be reset, since the next row looks at them.
+        row[first - 1] = if first == 1 {
+            cmp::min(i, too_far)
+        } else {
+            too_far
+        };
+        if last < pattern.len() {
+            row[last + 1] = too_far;
+        }
+        for j in first..=last {
+            let replace = above[j - 1].saturating_add((pattern[j - 1] != c) as usize);
+            let mut d = cmp::min(replace, cmp::min(above[j], row[j - 1]).saturating_add(1));
+            if allow_transpositions
+                && j > 1
+                && prev_c == Some(pattern[j - 1])
+                && c == pattern[j - 2]
+            {
+                d = cmp::min(d, above_that[j - 2].saturating_add(1));
+            }
+            row[j] = cmp::min(d, too_far);
+        }
+        // Once no prefix of the pattern is close enough, none ever will be
+        // again.
+        if row[first - 1..=last].iter().all(|&d| d == too_far) {
+            return None;
+        }
+        prev_c = Some(c);
+    }
+    Some(row[pattern.len()]).filter(|&d| d <= max_distance)
+}
+
+/// Returns the mask for every character in the pattern, sorted by character.
+///
+/// `CharMasks` fills in a table for all of Latin-1 up front, which is more
+/// work than comparing a couple of short strings, so this only has entries
+/// for the characters that are actually there. Any other character gets a
+/// mask with every bit set.
+fn char_masks<B: BitVector>(pattern: &str) -> Vec<(char, B)> {
+    let mut masks: Vec<(char, B)> = Vec::new();
+    for (i, c) in pattern.chars().enumerate() {
+        let bit = B::one() << i;
+        match masks.binary_search_by_key(&c, |&(mc, _)| mc) {
+            Ok(found) => masks[found].1 = masks[found].1 & !bit,
+            Err(at) => masks.insert(at, (c, !bit)),
+        }
+    }
+    masks
+}
@@ -0,0 +1,147 @@
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// A single step of an edit script, which describes how the pattern lines up
+/// with the text that it matched.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Edit {
+    /// A pattern character matched a text character.
+    Match,
+    /// A pattern character was replaced by a different text character.
+    Substitute,
+    /// A text character that isn't in the pattern was inserted.
+    Insert,
+    /// A pattern character was deleted, and doesn't appear in the text.
+    Delete,
+    /// Two adjacent pattern characters were swapped in the text. This is only
+    /// used by optimal string alignment distance.
+    Transpose,
+}
+
+impl Edit {
+    /// Returns the operation character used for this edit in a CIGAR string.
+    pub fn cigar_op(self) -> char {
+        match self {
+            Edit::Match => '=',
+            Edit::Substitute => 'X',
+            Edit::Insert => 'I',
+            Edit::Delete => 'D',
+            Edit::Transpose => 'T',
+        }
+    }
+}
+
+/// Renders an edit script as an extended CIGAR string, treating the pattern
+/// as the reference sequence.
+///
+/// Runs of the same edit are collapsed, so `[Match, Match, Substitute]`
+/// becomes `2=1X`. CIGAR has no operation for transpositions, so they are
+/// rendered as the non-standard `T`.
+///
+/// ```
+/// use bitap::{cigar, Edit};
+/// let edits = [Edit::Match, Edit::Match, Edit::Substitute, Edit::Match];
+/// assert_eq!(cigar(&edits), "2=1X1=");
+/// ```
+pub fn cigar(edits: &[Edit]) -> String {
+    let mut out = String::new();
+    let mut iter = edits.iter().peekable();
+    while let Some(&edit) = iter.next() {
+        let mut count = 1;
+        while iter.peek() == Some(&&edit) {
+            iter.next();
+            count += 1;
+        }
+        out.push_str(&count.to_string());
+        out.push(edit.cigar_op());
+    }
+    out
+}
+
+/// Computes the edit script for a match ending at the last character of the
+/// window, returning `None` if no alignment with exactly `distance` edits
+/// exists.
+///
+/// `accepts[j][i]` is whether window character `j` matches pattern position
+/// `i`, and `can_start(j)` is whether the alignment can start at window
+/// character `j`. Like the spans, when several alignments have the same
+/// distance, the longest one that starts in an allowed place is used.
+///
+/// This is a plain dynamic programming edit distance, rather than anything
+/// bitap specific, but it only runs over the handful of characters around a
+/// single match.
+pub(crate) fn edit_script<F: Fn(usize) -> bool>(
+    accepts: &[Vec<bool>],
+    pattern_length: usize,
+    distance: usize,
+    allow_transpositions: bool,
+    can_start: F,
+) -> Option<Vec<Edit>> {
+    let (m, w) = (pattern_length, accepts.len());
+    let eq = |i: usize, j: usize| accepts[j][i];
+
+    // d[i][j] is the distance between the pattern from position i and the
+    // window from position j, both running to the end.
+    let mut d = vec![vec![0; w + 1]; m + 1];
+    for i in (0..=m).rev() {
+        for j in (0..=w).rev() {
+            d[i][j] = if i == m {
+                w - j
+            } else if j == w {
+                m - i
+            } else {
+                let mut best = d[i + 1][j + 1] + if eq(i, j) { 0 } else { 1 };
+                best = best.min(d[i + 1][j] + 1).min(d[i][j + 1] + 1);
+                if allow_transpositions && transposed(&eq, i, j, m, w) {
+                    best = best.min(d[i + 2][j + 2] + 1);
+                }
+                best
+            };
+        }
+    }
+
+    let start = (0..w).find(|&j| d[0][j] == distance && can_start(j))?;
+
+    // Walk the table from the start of the alignment to the end, always
+    // picking an edit that keeps us on an optimal path.
+    let mut edits = Vec::new();
+    let (mut i, mut j) = (0, start);
+    while i < m || j < w {
+        let edit = if i < m && j < w && eq(i, j) && d[i][j] == d[i + 1][j + 1] {
+            Edit::Match
+        } else if i < m && j < w && d[i][j] == d[i + 1][j + 1] + 1 {
+            Edit::Substitute
+        } else if allow_transpositions
+            && transposed(&eq, i, j, m, w)
+            && d[i][j] == d[i + 2][j + 2] + 1
+        {
+            Edit::Transpose
+        } else if i < m && d[i][j] == d[i + 1][j] + 1 {
+            Edit::Delete
+        } else {
+            Edit::Insert
+        };
+        match edit {
+            Edit::Match | Edit::Substitute => {
+                i += 1;
+                j += 1;
+            }
+            Edit::Transpose => {
+                i += 2;
+                j += 2;
+            }
+            Edit::Delete => i += 1,
+            Edit::Insert => j += 1,
+        }
+        edits.push(edit);
+    }
+    Some(edits)
+}
+
+/// Returns whether pattern positions i and i + 1 appear swapped at window
+/// positions j and j + 1.
+#[inline]
+fn transposed<F: Fn(usize, usize) -> bool>(eq: &F, i: usize, j: usize, m: usize, w: usize) -> bool {
+    i + 1 < m && j + 1 < w && eq(i, j + 1) && eq(i + 1, j)
+}
@@ -0,0 +1,60 @@
+/// The implementation that `Pattern::lev` and `Pattern::osa` search with.
+///
+/// They all find exactly the same matches, they just differ in how fast
+/// they are for a given pattern and max distance. `Auto` picks whichever
+/// should be fastest, and the others force a particular one, which is
+/// mostly useful for benchmarking. See `PatternBuilder::engine`.
+#[derive(Copy, Clone, Debug, Default, Eq, Hash, PartialEq)]
+pub enum Engine {
+    /// Picks one of the others based on the max distance and the length of
+    /// the pattern.
+    #[default]
+    Auto,
+    /// The `find` adapter. Only used for a max distance of zero; any other
+    /// distance falls back to `Dynamic`.
+    Exact,
+    /// The `levenshtein_k` and `optimal_string_alignment_k` adapters, which
+    /// keep their state in fixed size arrays. Only used for max distances of
+    /// up to three; larger distances fall back to `Dynamic`.
+    Static,
+    /// The `levenshtein` and `optimal_string_alignment` adapters, which
+    /// keep a state vector for every number of edits.
+    Dynamic,
+    /// The `myers_levenshtein` and `myers_optimal_string_alignment`
+    /// adapters, which cost the same whatever the max distance is. They
+    /// can't only start matches at the edge of a word, so `Auto` uses
+    /// `Dynamic` instead for patterns that ask for that.
+    Myers,
+}
+
+impl Engine {
+    /// Returns the engine that will actually run a search with the passed
+    /// max distance, which must already be clamped to the most edits a match
+    /// can need, for a pattern that does or doesn't use `Wide` masks.
+    pub(crate) fn resolve(self, max_distance: usize, wide: bool) -> Engine {
+        match self {
+            Engine::Auto => select(max_distance, wide),
+            Engine::Exact if max_distance > 0 => Engine::Dynamic,
+            Engine::Static if max_distance > 3 => Engine::Dynamic,
+            engine => engine,
+        }
+    }
+}
+
+/// Picks the fastest engine for a search, going by the crossover points in
+/// the `bench_engines` benchmark.
+///
+/// The static arrays win for small distances, but each extra edit costs them
+/// another state vector, while Myers costs about the same as three of them
+/// whatever the distance. Wide masks make Myers' carrying addition slower,
+/// so it takes one more edit to pay off there. The dynamic adapter is never
+/// faster than one or the other. None of the engines do less work for rarer
+/// characters, so the alphabet doesn't come into it.
+fn select(max_distance: usize, wide: bool) -> Engine {
+    match max_distance {
+        0 => Engine::Exact,
+        1 | 2 => Engine::Static,
+        3 if wide => Engine::Static,
+        _ => Engine::Myers,
+    }
+}
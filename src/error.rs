@@ -0,0 +1,68 @@
+use core::fmt;
+
+/// The error type for everything that compiles or searches for a pattern.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum Error {
+    /// The pattern had no characters in it.
+    EmptyPattern,
+    /// The pattern was longer than the longest pattern that fits, which is
+    /// `MAX_PATTERN_LENGTH` for `Pattern` and one less than the number of
+    /// bits in the mask type for the iterator adapters. These limits are
+    /// deliberate, see `MAX_PATTERN_LENGTH`.
+    PatternTooLong {
+        /// The length of the pattern.
+        len: usize,
+        /// The longest pattern that would have been allowed.
+        max: usize,
+    },
+    /// The pattern wasn't valid character class syntax. See
+    /// `PatternBuilder::syntax`.
+    InvalidSyntax {
+        /// The index of the character where the invalid part of the pattern
+        /// starts.
+        position: usize,
+    },
+    /// The budget passed to `budgeted` allowed more combinations of edit
+    /// counts than `MAX_BUDGET_STATES`.
+    BudgetTooLarge {
+        /// The number of combinations, or `usize::MAX` if it doesn't fit.
+        states: usize,
+        /// The most combinations that would have been allowed.
+        max: usize,
+    },
+    /// The search can't restrict where matches start the way the pattern
+    /// asks. `Engine::Myers` and `Pattern::lev_myers` can only find matches
+    /// that start anywhere, or at the start of the text.
+    UnsupportedBounds,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::EmptyPattern => write!(f, "pattern is empty"),
+            Error::PatternTooLong { len, max } => write!(
+                f,
+                "pattern is {} characters long, but at most {} are allowed",
+                len, max
+            ),
+            Error::InvalidSyntax { position } => {
+                write!(f, "invalid pattern syntax at character {}", position)
+            }
+            Error::BudgetTooLarge { states, max } => write!(
+                f,
+                "edit budget allows {} combinations of edits, but at most {} are allowed",
+                states, max
+            ),
+            Error::UnsupportedBounds => {
+                write!(
+                    f,
+                    "search can't restrict where matches start like the pattern asks"
+                )
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {}
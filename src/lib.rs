@@ -1,6 +1,133 @@
-use std::cmp;
-use std::collections::HashMap;
-use std::mem;
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+use core::cmp;
+use core::iter;
+#[cfg(feature = "std")]
+use std::io::{self, Read};
+
+/// Runs the same expression against whichever mask representation a pattern
+/// uses, wrapping the resulting iterator in `Dispatch` so that every branch
+/// has the same type. This has to come before the modules that use it.
+macro_rules! dispatch {
+    ($kind:ident, $masks:expr, $m:ident => $e:expr) => {
+        match $masks {
+            $kind::Word($m) => Dispatch::Word($e),
+            $kind::Wide2($m) => Dispatch::Wide2($e),
+            $kind::Wide4($m) => Dispatch::Wide4($e),
+        }
+    };
+}
+
+/// Builds the state for whichever engine `Pattern::engine_for` picked and
+/// binds it to `$search` to run `$e`, wrapping the result in `ByEngine` so
+/// that every branch has the same type. `$start` is the `Boundary` matches
+/// have to start at, which bitap can skip some work for when it's anywhere,
+/// and Myers has to be told when it's the start of the text.
+macro_rules! by_engine {
+    (
+        $engine:expr,
+        $length:expr,
+        $max_distance:expr,
+        $allow_transpositions:expr,
+        $start:expr,
+        $search:ident => $e:expr
+    ) => {
+        match ($engine, $max_distance) {
+            (Engine::Exact, _) => {
+                let $search = Exact::new($length);
+                ByEngine::Exact($e)
+            }
+            (Engine::Static, 0) => {
+                let $search = Fixed::<_, 0>::new(
+                    $length,
+                    $allow_transpositions,
+                    $start == Boundary::Anywhere,
+                );
+                ByEngine::K0($e)
+            }
+            (Engine::Static, 1) => {
+                let $search = Fixed::<_, 1>::new(
+                    $length,
+                    $allow_transpositions,
+                    $start == Boundary::Anywhere,
+                );
+                ByEngine::K1($e)
+            }
+            (Engine::Static, 2) => {
+                let $search = Fixed::<_, 2>::new(
+                    $length,
+                    $allow_transpositions,
+                    $start == Boundary::Anywhere,
+                );
+                ByEngine::K2($e)
+            }
+            (Engine::Static, 3) => {
+                let $search = Fixed::<_, 3>::new(
+                    $length,
+                    $allow_transpositions,
+                    $start == Boundary::Anywhere,
+                );
+                ByEngine::K3($e)
+            }
+            (Engine::Myers, _) => {
+                let $search = if $start == Boundary::Text {
+                    Myers::anchored($length, $max_distance, $allow_transpositions)
+                } else {
+                    Myers::new($length, $max_distance, $allow_transpositions)
+                };
+                ByEngine::Myers($e)
+            }
+            _ => {
+                let anywhere = $start == Boundary::Anywhere;
+                let $search = Levels::new($length, $max_distance, $allow_transpositions, anywhere);
+                ByEngine::Dynamic($e)
+            }
+        }
+    };
+}
+
+mod bits;
+mod bounds;
+mod budget;
+mod builder;
+mod bytes;
+mod case;
+mod class;
+mod distance;
+mod edit;
+mod engine;
+mod error;
+mod masks;
+mod myers;
+#[cfg(feature = "std")]
+mod reader;
+mod set;
+mod span;
+mod step;
+mod stream;
+
+pub use crate::bits::{BitVector, Wide};
+pub use crate::bounds::Boundary;
+pub use crate::budget::{budgeted, BudgetMatch, EditCounts, TextBudgetMatch, MAX_BUDGET_STATES};
+pub use crate::builder::PatternBuilder;
+pub use crate::bytes::BytePattern;
+pub use crate::class::CharClass;
+pub use crate::distance::{levenshtein_distance, osa_distance, within_distance};
+pub use crate::edit::{cigar, Edit};
+pub use crate::engine::Engine;
+pub use crate::error::Error;
+pub use crate::myers::{myers_levenshtein, myers_optimal_string_alignment};
+pub use crate::set::PatternSet;
+pub use crate::span::MatchSpan;
+pub use crate::stream::Searcher;
+
+use crate::bounds::{BoundedMatches, Bounds};
+use crate::masks::CharMasks;
+use crate::myers::Myers;
+use crate::step::{Damerau, Exact, Fixed, Hamming, Levels, Prefix, Step};
 
 #[cfg(test)]
 extern crate quickcheck;
@@ -12,7 +139,7 @@ extern crate quickcheck_macros;
 #[macro_use]
 extern crate lazy_static;
 
-#[cfg(test)]
+#[cfg(all(test, feature = "std"))]
 mod test;
 
 /// Match represents a single match of a pattern within a string.
@@ -21,127 +148,154 @@ pub struct Match {
     /// The edit distance for this match. Zero means it was an exact match,
     /// one means a single edit, etc.
     pub distance: usize,
-    /// The index that this match _ends_ on. Determining start position isn't
-    /// possible (unless `distance` is zero), so this is all you have access to.
+    /// The index that this match _ends_ on. Bitap can't determine the start
+    /// position by itself (unless `distance` is zero), but `Pattern::lev_spans`
+    /// and `Pattern::osa_spans` can recover it with an extra reverse pass.
     pub end: usize,
 }
 
-static ERR_INVALID_PATTERN: &'static str = "invalid pattern length";
+/// TextMatch is a match found by `Pattern` in a string, along with where it
+/// ends in bytes, so that the text can be sliced without walking it again.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct TextMatch {
+    /// The edit distance for this match.
+    pub distance: usize,
+    /// The index of the character that this match _ends_ on.
+    pub end: usize,
+    /// The byte offset just past the last character of the match, so that
+    /// `&text[..byte_end]` ends with the match.
+    pub byte_end: usize,
+}
+
+impl From<TextMatch> for Match {
+    #[inline]
+    fn from(m: TextMatch) -> Match {
+        Match {
+            distance: m.distance,
+            end: m.end,
+        }
+    }
+}
 
-/// Returns whether the passed value is a valid pattern length.
+/// The longest pattern that `Pattern` can compile.
+///
+/// Patterns that fit in a single word are searched using `usize` masks (or
+/// whichever word type the pattern was built with), and anything longer
+/// transparently switches over to `Wide` masks.
+///
+/// The limit is deliberate. `Wide` masks are fixed-size arrays, so they're
+/// `Copy` and every step works on the stack without allocating, which a
+/// growable bit vector couldn't do. Patterns this long are rarely what you
+/// want to fuzzy search for anyway; the distance functions have no limit, so
+/// use those to compare long strings.
+pub const MAX_PATTERN_LENGTH: usize = <Wide<4> as BitVector>::BITS - 1;
+
+/// Returns whether the passed value is a valid pattern length for the
+/// iterator adapters when using `usize` masks.
 ///
 /// Because of implementation details of the bitap algorithm itself, patterns
 /// can only be as long as the system word size minus one. That's 31/63
 /// depending on the architecture you're compiling for. Additionally, patterns
-/// with a length of zero are rejected.
+/// with a length of zero are rejected. Other mask types allow patterns up to
+/// `BitVector::BITS - 1` long, so `u128` allows 127.
 #[inline]
 pub fn pattern_length_is_valid(pattern_length: usize) -> bool {
-    pattern_length > 0 && pattern_length < mem::size_of::<usize>() * 8
+    length_is_valid::<usize>(pattern_length)
+}
+
+/// Like `pattern_length_is_valid`, but for any mask type.
+#[inline]
+fn length_is_valid<B: BitVector>(pattern_length: usize) -> bool {
+    check_length::<B>(pattern_length).is_ok()
+}
+
+/// Returns why the passed value isn't a valid pattern length for any mask
+/// type, if it isn't.
+#[inline]
+fn check_length<B: BitVector>(pattern_length: usize) -> Result<(), Error> {
+    if pattern_length == 0 {
+        Err(Error::EmptyPattern)
+    } else if pattern_length >= B::BITS {
+        Err(Error::PatternTooLong {
+            len: pattern_length,
+            max: B::BITS - 1,
+        })
+    } else {
+        Ok(())
+    }
 }
 
 /// Iterator adapter for implementing bitap find over an iterator of pattern
 /// masks.
-pub fn find<I: Iterator<Item = usize>>(
+pub fn find<B: BitVector, I: Iterator<Item = B>>(
     mask_iter: I,
     pattern_length: usize,
-) -> Result<impl Iterator<Item = usize>, &'static str> {
-    if !pattern_length_is_valid(pattern_length) {
-        return Err(ERR_INVALID_PATTERN);
-    }
+) -> Result<impl Iterator<Item = usize>, Error> {
+    check_length::<B>(pattern_length)?;
     // In find, unlike the other functions, we want to return the _start_ index of the
     // matches because it's actually possible to recover.
     let offset = pattern_length - 1;
-    let mut r = !1usize;
-    let matches = mask_iter.enumerate().filter_map(move |(i, mask)| {
-        r |= mask;
-        r <<= 1;
-        if 0 == (r & (1usize << pattern_length)) {
-            return Some(i - offset);
-        }
-        None
-    });
+    let mut search = Exact::new(pattern_length);
+    let matches = mask_iter
+        .enumerate()
+        .filter_map(move |(i, mask)| search.step(mask, true).map(|_| i - offset));
     Ok(matches)
 }
 
 /// Iterator adapter for implementing bitap for levenshtein distance over an
 /// iterator of pattern masks.
-pub fn levenshtein<I: Iterator<Item = usize>>(
+pub fn levenshtein<B: BitVector, I: Iterator<Item = B>>(
     mask_iter: I,
     pattern_length: usize,
     max_distance: usize,
-) -> Result<impl Iterator<Item = Match>, &'static str> {
-    if !pattern_length_is_valid(pattern_length) {
-        return Err(ERR_INVALID_PATTERN);
-    }
-    let max_distance = cmp::min(max_distance, pattern_length);
-    let mut r: Vec<usize> = (0..=max_distance).map(|i| !1usize << i).collect();
-
-    let matches = mask_iter.enumerate().filter_map(move |(i, mask)| {
-        let mut prev_parent = r[0];
-        r[0] |= mask;
-        r[0] <<= 1;
-        for j in 1..r.len() {
-            let prev = r[j];
-            let current = (prev | mask) << 1;
-            let replace = prev_parent << 1;
-            let delete = r[j - 1] << 1;
-            let insert = prev_parent;
-            r[j] = current & insert & delete & replace;
-            prev_parent = prev;
-        }
-        for (k, rv) in r.iter().enumerate() {
-            if 0 == (rv & (1usize << pattern_length)) {
-                return Some(Match {
-                    distance: k,
-                    end: i,
-                });
-            }
-        }
-        None
-    });
-    Ok(matches)
+) -> Result<impl Iterator<Item = Match>, Error> {
+    check_length::<B>(pattern_length)?;
+    let search = Levels::new(pattern_length, max_distance, false, true);
+    Ok(step_matches(mask_iter, search))
 }
 
 /// Iterator adapter for implementing bitap for optimal string alignment
 /// distance over an iterator of pattern masks.
-pub fn optimal_string_alignment<I: Iterator<Item = usize>>(
+pub fn optimal_string_alignment<B: BitVector, I: Iterator<Item = B>>(
     mask_iter: I,
     pattern_length: usize,
     max_distance: usize,
-) -> Result<impl Iterator<Item = Match>, &'static str> {
-    if !pattern_length_is_valid(pattern_length) {
-        return Err(ERR_INVALID_PATTERN);
-    }
-    let max_distance = cmp::min(max_distance, pattern_length);
-    let mut r: Vec<usize> = (0..=max_distance).map(|i| !1usize << i).collect();
-    let mut t = vec![!1usize; max_distance];
+) -> Result<impl Iterator<Item = Match>, Error> {
+    check_length::<B>(pattern_length)?;
+    let search = Levels::new(pattern_length, max_distance, true, true);
+    Ok(step_matches(mask_iter, search))
+}
 
-    let matches = mask_iter.enumerate().filter_map(move |(i, mask)| {
-        let mut prev_parent = r[0];
-        r[0] |= mask;
-        r[0] <<= 1;
-        for j in 1..r.len() {
-            let prev = r[j];
-            let current = (prev | mask) << 1;
-            let replace = prev_parent << 1;
-            let delete = r[j - 1] << 1;
-            let insert = prev_parent;
-            let transpose = (t[j - 1] | (mask << 1)) << 1;
-            r[j] = current & insert & delete & replace & transpose;
-            t[j - 1] = (prev_parent << 1) | mask;
-            prev_parent = prev;
-        }
-        for (k, rv) in r.iter().enumerate() {
-            if 0 == (rv & (1usize << pattern_length)) {
-                return Some(Match {
-                    distance: k,
-                    end: i,
-                });
-            }
-        }
-        None
-    });
-    Ok(matches)
+/// Iterator adapter for implementing bitap for (unrestricted)
+/// damerau-levenshtein distance over an iterator of pattern masks.
+///
+/// Unlike optimal string alignment, the characters of a transposed pair can
+/// be edited further, like "ca" becoming "abc" with a transposition and an
+/// insertion. That means keeping track of transpositions that are still in
+/// progress, with either text inserted or pattern deleted between the two
+/// swapped characters, so this is slower than the other adapters and gets
+/// more so the larger `max_distance` is.
+pub fn damerau_levenshtein<B: BitVector, I: Iterator<Item = B>>(
+    mask_iter: I,
+    pattern_length: usize,
+    max_distance: usize,
+) -> Result<impl Iterator<Item = Match>, Error> {
+    check_length::<B>(pattern_length)?;
+    let search = Damerau::new(pattern_length, max_distance);
+    Ok(step_matches(mask_iter, search))
+}
+
+/// Iterator adapter for implementing bitap for hamming distance over an
+/// iterator of pattern masks. Only substitutions count as edits, so every
+/// match is exactly as long as the pattern.
+pub fn hamming<B: BitVector, I: Iterator<Item = B>>(
+    mask_iter: I,
+    pattern_length: usize,
+    max_distance: usize,
+) -> Result<impl Iterator<Item = Match>, Error> {
+    check_length::<B>(pattern_length)?;
+    let search = Hamming::new(pattern_length, max_distance);
+    Ok(step_matches(mask_iter, search))
 }
 
 pub enum StaticMaxDistance {
@@ -150,22 +304,20 @@ pub enum StaticMaxDistance {
 }
 
 /// Like the levenshtein iterator adapter, but optimized for max_distances of
-/// 1-2.
-pub fn levenshtein_static<I: Iterator<Item = usize>>(
+/// 1-2. See `levenshtein_k` for other distances.
+pub fn levenshtein_static<B: BitVector, I: Iterator<Item = B>>(
     mask_iter: I,
     pattern_length: usize,
     max_distance: StaticMaxDistance,
-) -> Result<impl Iterator<Item = Match>, &'static str> {
-    if !pattern_length_is_valid(pattern_length) {
-        return Err(ERR_INVALID_PATTERN);
-    }
+) -> Result<impl Iterator<Item = Match>, Error> {
+    check_length::<B>(pattern_length)?;
+    let match_bit = B::one() << pattern_length;
     let max_distance = cmp::min(max_distance as usize, pattern_length);
-    let mut r = [!1usize, !1usize << 1, !1usize << 2];
+    let mut r = [!B::one(), !B::one() << 1, !B::one() << 2];
 
     let matches = mask_iter.enumerate().filter_map(move |(i, mask)| {
         let mut prev_parent = r[0];
-        r[0] |= mask;
-        r[0] <<= 1;
+        r[0] = (r[0] | mask) << 1;
         for j in (1..r.len()).take(max_distance) {
             let prev = r[j];
             let current = (prev | mask) << 1;
@@ -176,7 +328,7 @@ pub fn levenshtein_static<I: Iterator<Item = usize>>(
             prev_parent = prev;
         }
         for (k, rv) in r.iter().take(max_distance + 1).enumerate() {
-            if 0 == (rv & (1usize << pattern_length)) {
+            if B::zero() == (*rv & match_bit) {
                 return Some(Match {
                     distance: k,
                     end: i,
@@ -190,22 +342,20 @@ pub fn levenshtein_static<I: Iterator<Item = usize>>(
 
 /// Like the optimal_string_alignment iterator adapter, but optimized for
 /// max_distances of 1-2.
-pub fn optimal_string_alignment_static<I: Iterator<Item = usize>>(
+pub fn optimal_string_alignment_static<B: BitVector, I: Iterator<Item = B>>(
     mask_iter: I,
     pattern_length: usize,
     max_distance: StaticMaxDistance,
-) -> Result<impl Iterator<Item = Match>, &'static str> {
-    if !pattern_length_is_valid(pattern_length) {
-        return Err(ERR_INVALID_PATTERN);
-    }
+) -> Result<impl Iterator<Item = Match>, Error> {
+    check_length::<B>(pattern_length)?;
+    let match_bit = B::one() << pattern_length;
     let max_distance = cmp::min(max_distance as usize, pattern_length);
-    let mut r = [!1usize, !1usize << 1, !1usize << 2];
-    let mut t = [!1usize, !1usize];
+    let mut r = [!B::one(), !B::one() << 1, !B::one() << 2];
+    let mut t = [!B::one(), !B::one()];
 
     let matches = mask_iter.enumerate().filter_map(move |(i, mask)| {
         let mut prev_parent = r[0];
-        r[0] |= mask;
-        r[0] <<= 1;
+        r[0] = (r[0] | mask) << 1;
         for j in (1..r.len()).take(max_distance) {
             let prev = r[j];
             let current = (prev | mask) << 1;
@@ -218,7 +368,7 @@ pub fn optimal_string_alignment_static<I: Iterator<Item = usize>>(
             prev_parent = prev;
         }
         for (k, rv) in r.iter().take(max_distance + 1).enumerate() {
-            if 0 == (rv & (1usize << pattern_length)) {
+            if B::zero() == (*rv & match_bit) {
                 return Some(Match {
                     distance: k,
                     end: i,
@@ -230,107 +380,721 @@ pub fn optimal_string_alignment_static<I: Iterator<Item = usize>>(
     Ok(matches)
 }
 
+/// Like the hamming iterator adapter, but optimized for max_distances of 1-2.
+pub fn hamming_static<B: BitVector, I: Iterator<Item = B>>(
+    mask_iter: I,
+    pattern_length: usize,
+    max_distance: StaticMaxDistance,
+) -> Result<impl Iterator<Item = Match>, Error> {
+    check_length::<B>(pattern_length)?;
+    let search = Hamming::new_static(pattern_length, max_distance as usize);
+    Ok(step_matches(mask_iter, search))
+}
+
+/// Like the levenshtein iterator adapter, but with the max distance fixed at
+/// compile time.
+///
+/// The matches are exactly the same as `levenshtein` with a `max_distance` of
+/// `K`, but the state lives in fixed size arrays rather than on the heap, so
+/// there's no allocation and the compiler can unroll the inner loop.
+/// `Pattern::lev` uses this automatically for small distances.
+///
+/// ```
+/// use bitap::{levenshtein_k,Match};
+/// // The masks for the pattern "abc" over the text "xbc".
+/// let masks = vec![!0usize, !0b010, !0b100];
+/// let m = levenshtein_k::<1, _>(masks.into_iter(), 3)?.collect::<Vec<_>>();
+/// assert_eq!(m, vec![Match{ distance: 1, end: 2 }]);
+/// # Ok::<(), bitap::Error>(())
+/// ```
+pub fn levenshtein_k<const K: usize, B: BitVector>(
+    mask_iter: impl Iterator<Item = B>,
+    pattern_length: usize,
+) -> Result<impl Iterator<Item = Match>, Error> {
+    check_length::<B>(pattern_length)?;
+    Ok(step_matches(
+        mask_iter,
+        Fixed::<B, K>::new(pattern_length, false, true),
+    ))
+}
+
+/// Like the optimal string alignment iterator adapter, but with the max
+/// distance fixed at compile time. See `levenshtein_k` for details.
+pub fn optimal_string_alignment_k<const K: usize, B: BitVector>(
+    mask_iter: impl Iterator<Item = B>,
+    pattern_length: usize,
+) -> Result<impl Iterator<Item = Match>, Error> {
+    check_length::<B>(pattern_length)?;
+    Ok(step_matches(
+        mask_iter,
+        Fixed::<B, K>::new(pattern_length, true, true),
+    ))
+}
+
+/// Iterator adapter for implementing bitap for levenshtein distance over an
+/// iterator of pattern masks, where matching the start of the pattern is
+/// enough.
+///
+/// This is for search-as-you-type, where the text is what the user has typed
+/// so far and the rest of the pattern just hasn't been typed yet. A match is
+/// reported wherever a prefix of the pattern at least `min_length`
+/// characters long (and never less than one) matched within a levenshtein
+/// distance of `max_distance`, along with the length of that prefix. The
+/// shortest prefixes match almost anywhere, so `min_length` should usually be
+/// more than `max_distance`. If several prefixes match at the same place, the
+/// one with the lowest distance is returned, and ties go to the longest.
+///
+/// ```
+/// use bitap::{levenshtein_prefix,Match};
+/// // The masks for the pattern "abcd" over the text "xabx".
+/// let masks = vec![!0usize, !0b0001, !0b0010, !0];
+/// let m = levenshtein_prefix(masks.into_iter(), 4, 0, 2)?.collect::<Vec<_>>();
+/// assert_eq!(m, vec![(2, Match{ distance: 0, end: 2 })]);
+/// # Ok::<(), bitap::Error>(())
+/// ```
+pub fn levenshtein_prefix<B: BitVector, I: Iterator<Item = B>>(
+    mask_iter: I,
+    pattern_length: usize,
+    max_distance: usize,
+    min_length: usize,
+) -> Result<impl Iterator<Item = (usize, Match)>, Error> {
+    check_length::<B>(pattern_length)?;
+    let mut search = Prefix::new(pattern_length, max_distance, min_length);
+    let matches = mask_iter.enumerate().filter_map(move |(i, mask)| {
+        let (consumed, distance) = search.step(mask, true)?;
+        Some((consumed, Match { distance, end: i }))
+    });
+    Ok(matches)
+}
+
+/// Runs a search over an iterator of masks, returning a match wherever it
+/// finds one.
+#[inline]
+fn step_matches<B, I, S>(mask_iter: I, mut search: S) -> impl Iterator<Item = Match>
+where
+    I: Iterator<Item = B>,
+    S: Step<B, Output = usize>,
+{
+    mask_iter.enumerate().filter_map(move |(i, mask)| {
+        let distance = search.step(mask, true)?;
+        Some(Match { distance, end: i })
+    })
+}
+
+/// Iterator adapter that filters the output of `levenshtein` or
+/// `optimal_string_alignment` (or their static variants, or the matching
+/// `Pattern` methods) down to local minima.
+///
+/// Every exact match is surrounded by one edit matches, which are surrounded
+/// by two edit matches, and so on. This collapses each of those runs of
+/// neighbouring matches into the single lowest distance match. If several
+/// neighbouring matches tie for the lowest distance, the first one is kept.
+/// Overlapping matches with a higher distance match between them, like "aba"
+/// in "ababa", are still returned separately.
+///
+/// ```
+/// use bitap::{local_minima,Match,Pattern};
+/// let pattern = Pattern::new("alex")?;
+/// let matches = local_minima(pattern.lev("hey im alex", 2)).map(Match::from);
+/// assert_eq!(matches.collect::<Vec<_>>(), vec![Match{ distance: 0, end: 10 }]);
+/// # Ok::<(), bitap::Error>(())
+/// ```
+pub fn local_minima<M, I>(matches: I) -> impl Iterator<Item = M>
+where
+    M: Copy + Into<Match>,
+    I: Iterator<Item = M>,
+{
+    let mut last: Option<Match> = None;
+    let mut candidate: Option<M> = None;
+    // The trailing None lets us flush the last candidate.
+    matches
+        .map(Some)
+        .chain(iter::once(None))
+        .filter_map(move |next| {
+            let (item, next) = match next {
+                Some(item) => (item, item.into()),
+                None => return candidate.take(),
+            };
+            match last.replace(next) {
+                Some(prev) if prev.end + 1 == next.end => {
+                    if next.distance < prev.distance {
+                        // Still heading downhill, so this is the new best.
+                        candidate = Some(item);
+                        None
+                    } else if next.distance > prev.distance {
+                        // Heading uphill, so whatever was best is a minimum.
+                        candidate.take()
+                    } else {
+                        None
+                    }
+                }
+                // Not a neighbour, so this starts a new run.
+                _ => candidate.replace(item),
+            }
+        })
+}
+
 /// A compiled pattern string that can be used to search text.
-pub struct Pattern {
+///
+/// Patterns that fit are searched using a single word of type `W` for their
+/// masks, and longer ones switch over to `Wide` masks. The default of `usize`
+/// is the fastest on most targets, but `u128` fits longer patterns in a
+/// single word, and `u32` keeps the state small. See
+/// `PatternBuilder::build_with_word`.
+pub struct Pattern<W = usize> {
     length: usize,
-    masks: HashMap<char, usize>,
+    masks: Masks<W>,
+    engine: Engine,
+    bounds: Bounds,
+}
+
+/// The pattern masks, stored using the narrowest bit vector that fits the
+/// pattern.
+enum Masks<W> {
+    Word(CharMasks<W>),
+    Wide2(CharMasks<Wide<2>>),
+    Wide4(CharMasks<Wide<4>>),
 }
 
 impl Pattern {
     /// Compiles and returns a new pattern from the passed string. Will fail
-    /// if the passed pattern is empty or longer than the system word size.
-    pub fn new(pattern: &str) -> Result<Pattern, &'static str> {
-        let mut length = 0;
-        // Create a mapping from characters to character masks. A "character's
-        // mask" in this case is a bitmask where, for every index that
-        // character is used in the pattern string, the value is zero.
-        //
-        // Roughly if the pattern were "abcab" the character masks would be as
-        // follows (albeit reversed, so the first character corresponds to the
-        // least significant bit). The remaining bits are all set to 1.
-        //
-        //        abcab abcab
-        //   "a": X..X. 01101
-        //   "b": .X..X 10110
-        //   "c": ..X.. 11011
-        //
-        let mut masks: HashMap<char, usize> = HashMap::new();
-        for (i, c) in pattern.chars().enumerate() {
-            length += 1;
-            masks
-                .entry(c)
-                .and_modify(|mask| *mask &= !(1usize << i))
-                .or_insert(!(1usize << i));
-        }
-        if !pattern_length_is_valid(length) {
-            return Err(ERR_INVALID_PATTERN);
-        }
-        Ok(Pattern { length, masks })
+    /// if the passed pattern is empty or longer than `MAX_PATTERN_LENGTH`.
+    ///
+    /// Use `PatternBuilder` to compile a pattern with non-default options.
+    pub fn new(pattern: &str) -> Result<Pattern, Error> {
+        PatternBuilder::new(pattern).build()
     }
+}
 
+impl<W: BitVector> Pattern<W> {
     /// Returns the length of the pattern in characters.
+    // Patterns can never be empty, so an `is_empty` method would be useless.
+    #[allow(clippy::len_without_is_empty)]
     #[inline]
     pub fn len(&self) -> usize {
         self.length
     }
 
-    #[inline]
-    fn mask_iter<'a>(&'a self, text: &'a str) -> MaskIterator<'a> {
-        MaskIterator {
-            masks: &self.masks,
-            iter: text.chars(),
+    /// Returns the same pattern, but backwards.
+    fn reversed(&self) -> Pattern<W> {
+        let length = self.length;
+        let masks = match &self.masks {
+            Masks::Word(masks) => Masks::Word(reverse_masks(masks, length)),
+            Masks::Wide2(masks) => Masks::Wide2(reverse_masks(masks, length)),
+            Masks::Wide4(masks) => Masks::Wide4(reverse_masks(masks, length)),
+        };
+        Pattern {
+            length,
+            masks,
+            engine: self.engine,
+            bounds: self.bounds,
         }
     }
 
-    /// Returns an iterator of character indexes where the pattern can be found
-    /// within the passed text.
+    /// Returns an iterator of spans where the pattern can be found exactly
+    /// within the passed text, and starts and ends where
+    /// `PatternBuilder::match_start` and `match_end` allow.
     ///
     /// Unlike `str::matches`, it will find and return overlapping matches.
     ///
     /// ```
     /// use bitap::{Pattern};
-    /// let pattern = Pattern::new("world")?;
-    /// assert_eq!(pattern.find("hello world").next(), Some(6));
-    /// # Ok::<(), &'static str>(())
+    /// let pattern = Pattern::new("wörld")?;
+    /// let text = "hellö wörld";
+    /// let m = pattern.find(text).next().unwrap();
+    /// assert_eq!((m.start, m.end), (6, 10));
+    /// assert_eq!(&text[m.byte_start..m.byte_end], "wörld");
+    /// # Ok::<(), bitap::Error>(())
+    /// ```
+    pub fn find<'a>(&'a self, text: &'a str) -> impl Iterator<Item = MatchSpan> + 'a {
+        let (length, bounds) = (self.len(), self.bounds);
+        let found = dispatch!(Masks, &self.masks, masks => {
+            BoundedMatches::new(masks, text, bounds, Exact::new(length))
+        });
+        found.map(move |(_, end, byte_end)| {
+            // Exact matches are always as long as the pattern, so the start is
+            // only ever that many characters back.
+            let (byte_start, _) = text[..byte_end]
+                .char_indices()
+                .rev()
+                .nth(length - 1)
+                .unwrap();
+            MatchSpan {
+                distance: 0,
+                start: end + 1 - length,
+                end,
+                byte_start,
+                byte_end,
+            }
+        })
+    }
+
+    /// Returns the engine that `lev` and `osa` search with for the passed
+    /// `max_distance`, which is the one set with `PatternBuilder::engine`
+    /// unless that's `Engine::Auto` or can't handle the distance.
+    /// `Engine::Auto` never picks `Engine::Myers` for patterns whose matches
+    /// have to start at the edge of a word, since it can't do that.
+    ///
+    /// ```
+    /// use bitap::{Engine,Pattern};
+    /// let pattern = Pattern::new("hello")?;
+    /// assert_eq!(pattern.engine_for(0), Engine::Exact);
+    /// assert_eq!(pattern.engine_for(1), Engine::Static);
+    /// # Ok::<(), bitap::Error>(())
     /// ```
-    pub fn find<'a>(&'a self, text: &'a str) -> impl Iterator<Item = usize> + 'a {
-        find(self.mask_iter(text), self.len()).unwrap()
+    pub fn engine_for(&self, max_distance: usize) -> Engine {
+        let wide = !matches!(self.masks, Masks::Word(_));
+        match self.engine.resolve(self.most_edits(max_distance), wide) {
+            // Forcing it is an error when the pattern is built.
+            Engine::Myers if self.bounds.start == Boundary::Word => Engine::Dynamic,
+            engine => engine,
+        }
+    }
+
+    /// Returns the most edits out of `max_distance` that a match can need.
+    ///
+    /// While a match can start anywhere, that's one per character of the
+    /// pattern, since its last character alone is that close. A match that
+    /// has to start somewhere in particular can need any number of them.
+    #[inline]
+    fn most_edits(&self, max_distance: usize) -> usize {
+        match self.bounds.start {
+            Boundary::Anywhere => cmp::min(max_distance, self.length),
+            _ => max_distance,
+        }
     }
 
     /// Returns an iterator of matches where the pattern matched the passed
     /// text within a levenshtein distance of `max_distance`.
     ///
     /// ```
-    /// use bitap::{Pattern,Match};
+    /// use bitap::{Pattern,TextMatch};
     /// let pattern = Pattern::new("wxrld")?;
     /// let m = pattern.lev("hello world", 1).next();
-    /// assert_eq!(m, Some(Match{ distance: 1, end: 10 }));
-    /// # Ok::<(), &'static str>(())
+    /// assert_eq!(m, Some(TextMatch{ distance: 1, end: 10, byte_end: 11 }));
+    /// # Ok::<(), bitap::Error>(())
     /// ```
     pub fn lev<'a>(
         &'a self,
         text: &'a str,
         max_distance: usize,
-    ) -> impl Iterator<Item = Match> + 'a {
-        levenshtein(self.mask_iter(text), self.len(), max_distance).unwrap()
+    ) -> impl Iterator<Item = TextMatch> + 'a {
+        self.search(text, max_distance, false)
     }
 
     /// Returns an iterator of matches where the pattern matched the passed
     /// text within an optimal string alignment distance of `max_distance`.
     ///
     /// ```
-    /// use bitap::{Pattern,Match};
+    /// use bitap::{Pattern,TextMatch};
     /// let pattern = Pattern::new("wrold")?;
     /// let m = pattern.osa("hello world", 1).next();
-    /// assert_eq!(m, Some(Match{ distance: 1, end: 10 }));
-    /// # Ok::<(), &'static str>(())
+    /// assert_eq!(m, Some(TextMatch{ distance: 1, end: 10, byte_end: 11 }));
+    /// # Ok::<(), bitap::Error>(())
     /// ```
     pub fn osa<'a>(
         &'a self,
         text: &'a str,
         max_distance: usize,
-    ) -> impl Iterator<Item = Match> + 'a {
-        optimal_string_alignment(self.mask_iter(text), self.len(), max_distance).unwrap()
+    ) -> impl Iterator<Item = TextMatch> + 'a {
+        self.search(text, max_distance, true)
+    }
+
+    fn search<'a>(
+        &'a self,
+        text: &'a str,
+        max_distance: usize,
+        allow_transpositions: bool,
+    ) -> impl Iterator<Item = TextMatch> + 'a {
+        let engine = self.engine_for(max_distance);
+        let max_distance = self.most_edits(max_distance);
+        let (length, bounds) = (self.len(), self.bounds);
+        let transpose = allow_transpositions;
+        dispatch!(Masks, &self.masks, masks => {
+            by_engine!(engine, length, max_distance, transpose, bounds.start, search => {
+                BoundedMatches::new(masks, text, bounds, search)
+            })
+        })
+        .map(text_match)
+    }
+
+    /// The same as lev, but using `myers_levenshtein`, which takes the same
+    /// time per character whatever `max_distance` is.
+    ///
+    /// Returns `Error::UnsupportedBounds` if matches have to start at the
+    /// edge of a word, which Myers can't do.
+    ///
+    /// ```
+    /// use bitap::{Pattern,TextMatch};
+    /// let pattern = Pattern::new("wxrld")?;
+    /// let m = pattern.lev_myers("hello world", 1)?.next();
+    /// assert_eq!(m, Some(TextMatch{ distance: 1, end: 10, byte_end: 11 }));
+    /// # Ok::<(), bitap::Error>(())
+    /// ```
+    pub fn lev_myers<'a>(
+        &'a self,
+        text: &'a str,
+        max_distance: usize,
+    ) -> Result<impl Iterator<Item = TextMatch> + 'a, Error> {
+        self.search_myers(text, max_distance, false)
+    }
+
+    /// The same as osa, but using `myers_optimal_string_alignment`, which
+    /// takes the same time per character whatever `max_distance` is. See
+    /// `lev_myers`.
+    pub fn osa_myers<'a>(
+        &'a self,
+        text: &'a str,
+        max_distance: usize,
+    ) -> Result<impl Iterator<Item = TextMatch> + 'a, Error> {
+        self.search_myers(text, max_distance, true)
+    }
+
+    fn search_myers<'a>(
+        &'a self,
+        text: &'a str,
+        max_distance: usize,
+        allow_transpositions: bool,
+    ) -> Result<impl Iterator<Item = TextMatch> + 'a, Error> {
+        if self.bounds.start == Boundary::Word {
+            return Err(Error::UnsupportedBounds);
+        }
+        let (length, bounds) = (self.len(), self.bounds);
+        let transpose = allow_transpositions;
+        let matches = dispatch!(Masks, &self.masks, masks => {
+            let search = if bounds.start == Boundary::Text {
+                Myers::anchored(length, max_distance, transpose)
+            } else {
+                Myers::new(length, max_distance, transpose)
+            };
+            BoundedMatches::new(masks, text, bounds, search)
+        })
+        .map(text_match);
+        Ok(matches)
+    }
+
+    /// Returns an iterator of `(consumed, match)` pairs for every place that
+    /// a prefix of the pattern at least `min_length` characters long matched
+    /// the passed text within a levenshtein distance of `max_distance`, where
+    /// `consumed` is the length of the prefix. See `levenshtein_prefix` for
+    /// details.
+    ///
+    /// ```
+    /// use bitap::{Pattern,TextMatch};
+    /// let pattern = Pattern::new("autocomplete")?;
+    /// let m = pattern.lev_prefix("search: avtoc", 1, 4).last();
+    /// assert_eq!(m, Some((5, TextMatch{ distance: 1, end: 12, byte_end: 13 })));
+    /// # Ok::<(), bitap::Error>(())
+    /// ```
+    pub fn lev_prefix<'a>(
+        &'a self,
+        text: &'a str,
+        max_distance: usize,
+        min_length: usize,
+    ) -> impl Iterator<Item = (usize, TextMatch)> + 'a {
+        let (length, bounds) = (self.len(), self.bounds);
+        let max_distance = self.most_edits(max_distance);
+        dispatch!(Masks, &self.masks, masks => {
+            let search = Prefix::new(length, max_distance, min_length);
+            BoundedMatches::new(masks, text, bounds, search)
+        })
+        .map(|((consumed, distance), end, byte_end)| {
+            let m = TextMatch {
+                distance,
+                end,
+                byte_end,
+            };
+            (consumed, m)
+        })
+    }
+
+    /// Returns an iterator of matches where the pattern matched the passed
+    /// text within a damerau-levenshtein distance of `max_distance`. Unlike
+    /// osa, characters can be inserted or deleted between a transposed pair.
+    ///
+    /// ```
+    /// use bitap::{Pattern,TextMatch};
+    /// let pattern = Pattern::new("abcdef")?;
+    /// let m = pattern.damerau("abdxcef", 2).next();
+    /// assert_eq!(m, Some(TextMatch{ distance: 2, end: 6, byte_end: 7 }));
+    /// assert_eq!(pattern.osa("abdxcef", 2).next(), None);
+    /// # Ok::<(), bitap::Error>(())
+    /// ```
+    pub fn damerau<'a>(
+        &'a self,
+        text: &'a str,
+        max_distance: usize,
+    ) -> impl Iterator<Item = TextMatch> + 'a {
+        let (length, bounds) = (self.len(), self.bounds);
+        let max_distance = self.most_edits(max_distance);
+        dispatch!(Masks, &self.masks, masks => {
+            BoundedMatches::new(masks, text, bounds, Damerau::new(length, max_distance))
+        })
+        .map(text_match)
+    }
+
+    /// Returns an iterator of matches where the pattern matched the passed
+    /// text using at most the passed number of each kind of edit. See
+    /// `budgeted` for details.
+    ///
+    /// Returns an error if the budget allows more than `MAX_BUDGET_STATES`
+    /// combinations of edits.
+    ///
+    /// ```
+    /// use bitap::{EditCounts,Pattern};
+    /// let pattern = Pattern::new("receipt")?;
+    /// let budget = EditCounts{ deletions: 2, substitutions: 1, ..EditCounts::default() };
+    /// let m = pattern.budgeted("total on reciet", budget)?.last().unwrap();
+    /// assert_eq!(m.edits, EditCounts{ deletions: 1, substitutions: 1, ..EditCounts::default() });
+    /// assert_eq!(m.byte_end, 15);
+    /// # Ok::<(), bitap::Error>(())
+    /// ```
+    pub fn budgeted<'a>(
+        &'a self,
+        text: &'a str,
+        budget: EditCounts,
+    ) -> Result<impl Iterator<Item = TextBudgetMatch> + 'a, Error> {
+        let budget = budget::clamp_budget(budget, self.len())?;
+        let (length, bounds) = (self.len(), self.bounds);
+        let matches = dispatch!(Masks, &self.masks, masks => {
+            BoundedMatches::new(masks, text, bounds, budget::Budgeted::new(length, budget))
+        })
+        .map(|(edits, end, byte_end)| TextBudgetMatch {
+            edits,
+            end,
+            byte_end,
+        });
+        Ok(matches)
+    }
+
+    /// Returns an iterator of matches where the pattern matched the passed
+    /// text within a hamming distance of `max_distance`. Only substitutions
+    /// are allowed, so each match starts `len() - 1` characters before its
+    /// end.
+    ///
+    /// ```
+    /// use bitap::{Pattern,TextMatch};
+    /// let pattern = Pattern::new("AB-1234")?;
+    /// let m = pattern.hamming("plate AB-1284, AB-234", 1).collect::<Vec<_>>();
+    /// assert_eq!(m, vec![TextMatch{ distance: 1, end: 12, byte_end: 13 }]);
+    /// # Ok::<(), bitap::Error>(())
+    /// ```
+    pub fn hamming<'a>(
+        &'a self,
+        text: &'a str,
+        max_distance: usize,
+    ) -> impl Iterator<Item = TextMatch> + 'a {
+        let (length, bounds) = (self.len(), self.bounds);
+        dispatch!(Masks, &self.masks, masks => {
+            BoundedMatches::new(masks, text, bounds, Hamming::new(length, max_distance))
+        })
+        .map(text_match)
+    }
+
+    /// Like lev, but only returns the best match out of each run of
+    /// neighbouring matches. See `local_minima` for details.
+    ///
+    /// ```
+    /// use bitap::{Match,Pattern};
+    /// let pattern = Pattern::new("wxrld")?;
+    /// let m = pattern.lev_best("hello world, hello wxrld", 2).map(Match::from);
+    /// let m = m.collect::<Vec<_>>();
+    /// assert_eq!(m, vec![Match{ distance: 1, end: 10 }, Match{ distance: 0, end: 23 }]);
+    /// # Ok::<(), bitap::Error>(())
+    /// ```
+    pub fn lev_best<'a>(
+        &'a self,
+        text: &'a str,
+        max_distance: usize,
+    ) -> impl Iterator<Item = TextMatch> + 'a {
+        local_minima(self.lev(text, max_distance))
+    }
+
+    /// Like osa, but only returns the best match out of each run of
+    /// neighbouring matches. See `local_minima` for details.
+    pub fn osa_best<'a>(
+        &'a self,
+        text: &'a str,
+        max_distance: usize,
+    ) -> impl Iterator<Item = TextMatch> + 'a {
+        local_minima(self.osa(text, max_distance))
+    }
+
+    /// Like lev, but also recovers where each match starts.
+    ///
+    /// When several alignments ending at the same place have the same
+    /// distance, the start of the longest one is returned.
+    ///
+    /// ```
+    /// use bitap::{Pattern,MatchSpan};
+    /// let pattern = Pattern::new("wxrld")?;
+    /// let m = pattern.lev_spans("hello world", 1).next();
+    /// let span = MatchSpan{ distance: 1, start: 6, end: 10, byte_start: 6, byte_end: 11 };
+    /// assert_eq!(m, Some(span));
+    /// # Ok::<(), bitap::Error>(())
+    /// ```
+    pub fn lev_spans<'a>(
+        &'a self,
+        text: &'a str,
+        max_distance: usize,
+    ) -> impl Iterator<Item = MatchSpan> + 'a {
+        let matches = self.lev(text, max_distance);
+        span::Spans::new(matches, text, self.reversed(), false, self.bounds)
+    }
+
+    /// Like osa, but also recovers where each match starts.
+    ///
+    /// When several alignments ending at the same place have the same
+    /// distance, the start of the longest one is returned.
+    ///
+    /// ```
+    /// use bitap::{Pattern,MatchSpan};
+    /// let pattern = Pattern::new("wrold")?;
+    /// let m = pattern.osa_spans("hello world", 1).next();
+    /// let span = MatchSpan{ distance: 1, start: 6, end: 10, byte_start: 6, byte_end: 11 };
+    /// assert_eq!(m, Some(span));
+    /// # Ok::<(), bitap::Error>(())
+    /// ```
+    pub fn osa_spans<'a>(
+        &'a self,
+        text: &'a str,
+        max_distance: usize,
+    ) -> impl Iterator<Item = MatchSpan> + 'a {
+        let matches = self.osa(text, max_distance);
+        span::Spans::new(matches, text, self.reversed(), true, self.bounds)
+    }
+
+    /// Returns a searcher that finds the same matches as `lev`, but over text
+    /// that's fed to it a chunk at a time. See `Searcher` for details.
+    pub fn lev_searcher(&self, max_distance: usize) -> Searcher<'_, W> {
+        Searcher::new(self, max_distance, false)
+    }
+
+    /// Returns a searcher that finds the same matches as `osa`, but over text
+    /// that's fed to it a chunk at a time.
+    pub fn osa_searcher(&self, max_distance: usize) -> Searcher<'_, W> {
+        Searcher::new(self, max_distance, true)
+    }
+
+    /// Returns an iterator of matches where the pattern matched the text read
+    /// from `reader` within a levenshtein distance of `max_distance`.
+    ///
+    /// The text is read a buffer at a time, so there's no need to wrap the
+    /// reader in a `BufReader`, and characters that are split between reads
+    /// are stitched back together. Positions are counted from the start of
+    /// the reader. If reading fails or the text isn't valid UTF-8, the error
+    /// is returned and the iterator ends.
+    ///
+    /// ```
+    /// use bitap::{Pattern,TextMatch};
+    /// let pattern = Pattern::new("wxrld")?;
+    /// let mut m = pattern.lev_reader("hello world".as_bytes(), 1);
+    /// assert_eq!(m.next().unwrap()?, TextMatch{ distance: 1, end: 10, byte_end: 11 });
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn lev_reader<'a, R: Read + 'a>(
+        &'a self,
+        reader: R,
+        max_distance: usize,
+    ) -> impl Iterator<Item = io::Result<TextMatch>> + 'a {
+        reader::ReaderMatches::new(self.lev_searcher(max_distance), reader)
+    }
+
+    /// Like `lev_reader`, but for optimal string alignment distance.
+    #[cfg(feature = "std")]
+    pub fn osa_reader<'a, R: Read + 'a>(
+        &'a self,
+        reader: R,
+        max_distance: usize,
+    ) -> impl Iterator<Item = io::Result<TextMatch>> + 'a {
+        reader::ReaderMatches::new(self.osa_searcher(max_distance), reader)
+    }
+
+    /// Returns the edit script for a match that `lev` found in the passed
+    /// text, describing exactly which edits turn the pattern into the matched
+    /// text. Returns `None` if the match didn't come from this text.
+    ///
+    /// The script covers the same alignment as `lev_spans`, so it starts at
+    /// the match's start.
+    ///
+    /// ```
+    /// use bitap::{cigar,Edit,Pattern};
+    /// let pattern = Pattern::new("wxrld")?;
+    /// let m = pattern.lev("hello world", 1).next().unwrap();
+    /// let edits = pattern.lev_edits("hello world", m).unwrap();
+    /// assert_eq!(edits[1], Edit::Substitute);
+    /// assert_eq!(cigar(&edits), "1=1X3=");
+    /// # Ok::<(), bitap::Error>(())
+    /// ```
+    pub fn lev_edits(&self, text: &str, m: TextMatch) -> Option<Vec<Edit>> {
+        self.edits(text, m, false)
+    }
+
+    /// Returns the edit script for a match that `osa` found in the passed
+    /// text. See `lev_edits` for details.
+    ///
+    /// ```
+    /// use bitap::{cigar,Pattern};
+    /// let pattern = Pattern::new("wrold")?;
+    /// let m = pattern.osa("hello world", 1).next().unwrap();
+    /// let edits = pattern.osa_edits("hello world", m).unwrap();
+    /// assert_eq!(cigar(&edits), "1=1T2=");
+    /// # Ok::<(), bitap::Error>(())
+    /// ```
+    pub fn osa_edits(&self, text: &str, m: TextMatch) -> Option<Vec<Edit>> {
+        self.edits(text, m, true)
+    }
+
+    fn edits(&self, text: &str, m: TextMatch, allow_transpositions: bool) -> Option<Vec<Edit>> {
+        // No alignment can be longer than the pattern plus the number of
+        // insertions, so that's all the text we need to look at.
+        let window = cmp::min(m.end + 1, self.length + m.distance);
+        // Keep the character before the window too, to check where the
+        // alignment can start.
+        let mut chars = text
+            .get(..m.byte_end)?
+            .chars()
+            .rev()
+            .take(window + 1)
+            .collect::<Vec<_>>();
+        let before = if chars.len() > window {
+            chars.pop()
+        } else {
+            None
+        };
+        if chars.len() != window {
+            return None;
+        }
+        chars.reverse();
+        let accepts = chars.iter().map(|&c| self.accepts(c)).collect::<Vec<_>>();
+        let can_start = |j: usize| {
+            let prev = if j == 0 { before } else { Some(chars[j - 1]) };
+            self.bounds.can_start_after(prev)
+        };
+        edit::edit_script(
+            &accepts,
+            self.length,
+            m.distance,
+            allow_transpositions,
+            can_start,
+        )
+    }
+
+    /// Returns whether each position of the pattern matches the passed
+    /// character.
+    fn accepts(&self, c: char) -> Vec<bool> {
+        fn unpack<B: BitVector>(masks: &CharMasks<B>, c: char, length: usize) -> Vec<bool> {
+            let mask = masks.get(c);
+            (0..length)
+                .map(|i| B::zero() == (mask & (B::one() << i)))
+                .collect()
+        }
+        match &self.masks {
+            Masks::Word(masks) => unpack(masks, c, self.length),
+            Masks::Wide2(masks) => unpack(masks, c, self.length),
+            Masks::Wide4(masks) => unpack(masks, c, self.length),
+        }
     }
 
     /// The same as lev, but optimized for a `max_distance` of 1-2.
@@ -338,8 +1102,8 @@ impl Pattern {
         &'a self,
         text: &'a str,
         max_distance: StaticMaxDistance,
-    ) -> impl Iterator<Item = Match> + 'a {
-        levenshtein_static(self.mask_iter(text), self.len(), max_distance).unwrap()
+    ) -> impl Iterator<Item = TextMatch> + 'a {
+        self.search_static(text, max_distance, false)
     }
 
     /// The same as osa, but optimized for a `max_distance` of 1-2.
@@ -347,26 +1111,75 @@ impl Pattern {
         &'a self,
         text: &'a str,
         max_distance: StaticMaxDistance,
-    ) -> impl Iterator<Item = Match> + 'a {
-        optimal_string_alignment_static(self.mask_iter(text), self.len(), max_distance).unwrap()
+    ) -> impl Iterator<Item = TextMatch> + 'a {
+        self.search_static(text, max_distance, true)
+    }
+
+    fn search_static<'a>(
+        &'a self,
+        text: &'a str,
+        max_distance: StaticMaxDistance,
+        allow_transpositions: bool,
+    ) -> impl Iterator<Item = TextMatch> + 'a {
+        let max_distance = self.most_edits(max_distance as usize);
+        let (length, bounds) = (self.len(), self.bounds);
+        let transpose = allow_transpositions;
+        dispatch!(Masks, &self.masks, masks => {
+            by_engine!(Engine::Static, length, max_distance, transpose, bounds.start, search => {
+                BoundedMatches::new(masks, text, bounds, search)
+            })
+        })
+        .map(text_match)
     }
+
+    /// The same as hamming, but optimized for a `max_distance` of 1-2.
+    pub fn hamming_static<'a>(
+        &'a self,
+        text: &'a str,
+        max_distance: StaticMaxDistance,
+    ) -> impl Iterator<Item = TextMatch> + 'a {
+        let (length, bounds) = (self.len(), self.bounds);
+        dispatch!(Masks, &self.masks, masks => {
+            let search = Hamming::new_static(length, max_distance as usize);
+            BoundedMatches::new(masks, text, bounds, search)
+        })
+        .map(text_match)
+    }
+}
+
+/// Returns the masks for the reversed pattern.
+fn reverse_masks<B: BitVector>(masks: &CharMasks<B>, length: usize) -> CharMasks<B> {
+    masks.map(|mask| {
+        let mut reversed = !B::zero();
+        for i in 0..length {
+            if B::zero() == (mask & (B::one() << i)) {
+                reversed = reversed & !(B::one() << (length - 1 - i));
+            }
+        }
+        reversed
+    })
+}
+
+#[inline]
+fn mask_iter<B: BitVector, C: Iterator<Item = char>>(
+    masks: &CharMasks<B>,
+    chars: C,
+) -> MaskIterator<'_, B, C> {
+    MaskIterator { masks, iter: chars }
 }
 
 /// Combines the mask map and an iterator of chars into a stream of pattern masks.
-struct MaskIterator<'a> {
-    masks: &'a HashMap<char, usize>,
-    iter: std::str::Chars<'a>,
+struct MaskIterator<'a, B, C> {
+    masks: &'a CharMasks<B>,
+    iter: C,
 }
 
-impl<'a> Iterator for MaskIterator<'a> {
-    type Item = usize;
+impl<'a, B: BitVector, C: Iterator<Item = char>> Iterator for MaskIterator<'a, B, C> {
+    type Item = B;
 
     #[inline]
     fn next(&mut self) -> Option<Self::Item> {
-        self.iter.next().map(|c| match self.masks.get(&c) {
-            Some(m) => *m,
-            None => !0usize,
-        })
+        self.iter.next().map(|c| self.masks.get(c))
     }
 
     #[inline]
@@ -374,3 +1187,109 @@ impl<'a> Iterator for MaskIterator<'a> {
         self.iter.size_hint()
     }
 }
+
+/// Turns what `BoundedMatches` found for a search that returns a distance into a
+/// match.
+#[inline]
+fn text_match((distance, end, byte_end): (usize, usize, usize)) -> TextMatch {
+    TextMatch {
+        distance,
+        end,
+        byte_end,
+    }
+}
+
+/// An iterator over the results of whichever engine `by_engine!` picked, or
+/// the state of it.
+enum ByEngine<X, A, B, C, D, E, M> {
+    Exact(X),
+    K0(A),
+    K1(B),
+    K2(C),
+    K3(D),
+    Dynamic(E),
+    Myers(M),
+}
+
+impl<T, X, A, B, C, D, E, M> Iterator for ByEngine<X, A, B, C, D, E, M>
+where
+    X: Iterator<Item = T>,
+    A: Iterator<Item = T>,
+    B: Iterator<Item = T>,
+    C: Iterator<Item = T>,
+    D: Iterator<Item = T>,
+    E: Iterator<Item = T>,
+    M: Iterator<Item = T>,
+{
+    type Item = T;
+
+    #[inline]
+    fn next(&mut self) -> Option<T> {
+        match self {
+            ByEngine::Exact(iter) => iter.next(),
+            ByEngine::K0(iter) => iter.next(),
+            ByEngine::K1(iter) => iter.next(),
+            ByEngine::K2(iter) => iter.next(),
+            ByEngine::K3(iter) => iter.next(),
+            ByEngine::Dynamic(iter) => iter.next(),
+            ByEngine::Myers(iter) => iter.next(),
+        }
+    }
+}
+
+/// The state of whichever engine `by_engine!` picked, for searches that
+/// hold on to it rather than running it over a whole string.
+type Search<V> =
+    ByEngine<Exact<V>, Fixed<V, 0>, Fixed<V, 1>, Fixed<V, 2>, Fixed<V, 3>, Levels<V>, Myers<V>>;
+
+impl<V, T, X, A, B, C, D, E, M> Step<V> for ByEngine<X, A, B, C, D, E, M>
+where
+    X: Step<V, Output = T>,
+    A: Step<V, Output = T>,
+    B: Step<V, Output = T>,
+    C: Step<V, Output = T>,
+    D: Step<V, Output = T>,
+    E: Step<V, Output = T>,
+    M: Step<V, Output = T>,
+{
+    type Output = T;
+
+    #[inline]
+    fn step(&mut self, mask: V, can_start: bool) -> Option<T> {
+        match self {
+            ByEngine::Exact(search) => search.step(mask, can_start),
+            ByEngine::K0(search) => search.step(mask, can_start),
+            ByEngine::K1(search) => search.step(mask, can_start),
+            ByEngine::K2(search) => search.step(mask, can_start),
+            ByEngine::K3(search) => search.step(mask, can_start),
+            ByEngine::Dynamic(search) => search.step(mask, can_start),
+            ByEngine::Myers(search) => search.step(mask, can_start),
+        }
+    }
+}
+
+/// An iterator over the results of whichever mask representation a pattern
+/// uses; see the `dispatch!` macro.
+enum Dispatch<A, B, C> {
+    Word(A),
+    Wide2(B),
+    Wide4(C),
+}
+
+impl<T, A, B, C> Iterator for Dispatch<A, B, C>
+where
+    A: Iterator<Item = T>,
+    B: Iterator<Item = T>,
+    C: Iterator<Item = T>,
+{
+    type Item = T;
+
+    #[inline]
+    fn next(&mut self) -> Option<T> {
+        match self {
+            Dispatch::Word(iter) => iter.next(),
+            Dispatch::Wide2(iter) => iter.next(),
+            Dispatch::Wide4(iter) => iter.next(),
+        }
+    }
+}
@@ -0,0 +1,167 @@
+use alloc::boxed::Box;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+
+use crate::class::CharClass;
+use crate::BitVector;
+
+/// The pattern mask for every character.
+///
+/// Looking up the mask for each character of the text is the hot loop of
+/// every search, so this is optimized for the common case: masks for ASCII
+/// and Latin-1 characters are stored in a table indexed directly by code
+/// point, and only the rare characters beyond that fall back to a binary
+/// search through a sorted list of code point ranges. Any character that
+/// isn't in either gets the default mask.
+pub(crate) struct CharMasks<B> {
+    latin1: Box<[B; 256]>,
+    other: Vec<(u32, u32, B)>,
+    default: B,
+}
+
+impl<B: BitVector> CharMasks<B> {
+    /// Creates a mapping from characters to character masks. A "character's
+    /// mask" in this case is a bitmask where, for every index of the pattern
+    /// that accepts that character, the value is zero.
+    ///
+    /// Roughly if the pattern were "abcab" the character masks would be as
+    /// follows (albeit reversed, so the first character corresponds to the
+    /// least significant bit). The remaining bits are all set to 1.
+    ///
+    /// ```text
+    ///        abcab abcab
+    ///   "a": X..X. 01101
+    ///   "b": .X..X 10110
+    ///   "c": ..X.. 11011
+    /// ```
+    ///
+    /// Positions that accept any character, or any character outside of a
+    /// negated set, are zero in the default mask as well. When matching is
+    /// case insensitive, every case variant of a character in a class is in
+    /// the class too.
+    ///
+    /// Text wildcards are accepted by every position, so their masks are all
+    /// zeros.
+    pub(crate) fn new(
+        classes: &[CharClass],
+        case_insensitive: bool,
+        text_wildcards: &[char],
+    ) -> CharMasks<B> {
+        // Patterns often repeat a class, like `[a-z]{10}`, so each distinct
+        // class only has its ranges worked out once.
+        let mut sets: Vec<(Vec<(u32, u32)>, bool)> = Vec::with_capacity(classes.len());
+        for (i, class) in classes.iter().enumerate() {
+            let set = match classes[..i].iter().position(|c| c == class) {
+                Some(same) => sets[same].clone(),
+                None => class.ranges(case_insensitive),
+            };
+            sets.push(set);
+        }
+
+        let mut default = !B::zero();
+        for (i, &(_, negated)) in sets.iter().enumerate() {
+            if negated {
+                default = default & !(B::one() << i);
+            }
+        }
+
+        let mut latin1 = Box::new([default; 256]);
+        for (i, (ranges, negated)) in sets.iter().enumerate() {
+            let bit = B::one() << i;
+            for &(start, end) in ranges.iter().filter(|&&(start, _)| start < 256) {
+                for mask in &mut latin1[start as usize..=end.min(255) as usize] {
+                    *mask = if *negated { *mask | bit } else { *mask & !bit };
+                }
+            }
+        }
+        let wildcards = text_wildcards.iter().map(|&c| c as u32).collect::<Vec<_>>();
+        for &c in wildcards.iter().filter(|&&c| c < 256) {
+            latin1[c as usize] = B::zero();
+        }
+
+        // Every range boundary beyond Latin-1 splits the rest of the code
+        // points into intervals that all share a mask.
+        let mut bounds = sets
+            .iter()
+            .flat_map(|(ranges, _)| ranges.iter().copied())
+            .chain(wildcards.iter().map(|c| (*c, *c)))
+            .flat_map(|(start, end)| vec![start.max(256), end.saturating_add(1).max(256)])
+            .collect::<Vec<_>>();
+        bounds.sort();
+        bounds.dedup();
+        let mut other = Vec::new();
+        for pair in bounds.windows(2) {
+            if wildcards.contains(&pair[0]) {
+                other.push((pair[0], pair[0], B::zero()));
+                continue;
+            }
+            let mut mask = default;
+            for (i, (ranges, negated)) in sets.iter().enumerate() {
+                if contains(ranges, pair[0]) {
+                    let bit = B::one() << i;
+                    mask = if *negated { mask | bit } else { mask & !bit };
+                }
+            }
+            if mask != default {
+                other.push((pair[0], pair[1] - 1, mask));
+            }
+        }
+
+        CharMasks {
+            latin1,
+            other,
+            default,
+        }
+    }
+
+    /// Returns the mask for the passed character.
+    #[inline]
+    pub(crate) fn get(&self, c: char) -> B {
+        match self.latin1.get(c as usize) {
+            Some(mask) => *mask,
+            None => {
+                let c = c as u32;
+                let found = self.other.binary_search_by(|&(start, end, _)| {
+                    if end < c {
+                        Ordering::Less
+                    } else if start > c {
+                        Ordering::Greater
+                    } else {
+                        Ordering::Equal
+                    }
+                });
+                match found {
+                    Ok(i) => self.other[i].2,
+                    Err(_) => self.default,
+                }
+            }
+        }
+    }
+
+    /// Returns a copy of the masks with `f` applied to every one of them.
+    pub(crate) fn map<F: Fn(B) -> B>(&self, f: F) -> CharMasks<B> {
+        let mut latin1 = self.latin1.clone();
+        for mask in latin1.iter_mut() {
+            *mask = f(*mask);
+        }
+        let other = self
+            .other
+            .iter()
+            .map(|&(start, end, mask)| (start, end, f(mask)))
+            .collect();
+        CharMasks {
+            latin1,
+            other,
+            default: f(self.default),
+        }
+    }
+}
+
+fn contains(ranges: &[(u32, u32)], c: u32) -> bool {
+    match ranges.binary_search_by_key(&c, |&(start, _)| start) {
+        Ok(_) => true,
+        Err(0) => false,
+        Err(i) => ranges[i - 1].1 >= c,
+    }
+}
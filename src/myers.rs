@@ -0,0 +1,189 @@
+use crate::step::Step;
+use crate::{check_length, step_matches, BitVector, Error, Match};
+
+/// Iterator adapter for finding matches within a levenshtein distance of
+/// `max_distance` with Myers' bit-vector algorithm, over an iterator of
+/// pattern masks.
+///
+/// Rather than a state vector for every number of edits like `levenshtein`,
+/// this keeps a single column of the edit distance table, encoded as the
+/// differences between neighbouring cells, along with the distance at the
+/// bottom of it. That makes every character of the text cost the same
+/// handful of word operations however big `max_distance` is, so it wins
+/// over `levenshtein` once the distance gets large. The matches are exactly
+/// the same as `levenshtein`'s.
+///
+/// ```
+/// use bitap::{myers_levenshtein,Match};
+/// // The masks for the pattern "abc" over the text "xbc".
+/// let masks = vec![!0usize, !0b010, !0b100];
+/// let m = myers_levenshtein(masks.into_iter(), 3, 1)?.collect::<Vec<_>>();
+/// assert_eq!(m, vec![Match{ distance: 1, end: 2 }]);
+/// # Ok::<(), bitap::Error>(())
+/// ```
+pub fn myers_levenshtein<B: BitVector, I: Iterator<Item = B>>(
+    mask_iter: I,
+    pattern_length: usize,
+    max_distance: usize,
+) -> Result<impl Iterator<Item = Match>, Error> {
+    check_length::<B>(pattern_length)?;
+    let search = Myers::new(pattern_length, max_distance, false);
+    Ok(step_matches(mask_iter, search))
+}
+
+/// Iterator adapter for finding matches within an optimal string alignment
+/// distance of `max_distance` with Myers' bit-vector algorithm, using
+/// Hyyrö's extension for transpositions. See `myers_levenshtein` for
+/// details; the matches are exactly the same as `optimal_string_alignment`'s.
+pub fn myers_optimal_string_alignment<B: BitVector, I: Iterator<Item = B>>(
+    mask_iter: I,
+    pattern_length: usize,
+    max_distance: usize,
+) -> Result<impl Iterator<Item = Match>, Error> {
+    check_length::<B>(pattern_length)?;
+    let search = Myers::new(pattern_length, max_distance, true);
+    Ok(step_matches(mask_iter, search))
+}
+
+/// The state of `myers_levenshtein` and `myers_optimal_string_alignment`.
+///
+/// The column decides where a match can start, so this ignores what it's
+/// told when it's stepped: either anywhere, or only at the start of the text
+/// with an anchored column. It can't handle anything else.
+pub(crate) struct Myers<B> {
+    column: Column<B>,
+    max_distance: usize,
+    allow_transpositions: bool,
+    prev_eq: B,
+    prev_d0: B,
+}
+
+impl<B: BitVector> Myers<B> {
+    pub(crate) fn new(
+        pattern_length: usize,
+        max_distance: usize,
+        allow_transpositions: bool,
+    ) -> Myers<B> {
+        Myers::with_column(
+            Column::new(pattern_length),
+            max_distance,
+            allow_transpositions,
+        )
+    }
+
+    /// Like `new`, but for matches that have to start at the start of the
+    /// text.
+    pub(crate) fn anchored(
+        pattern_length: usize,
+        max_distance: usize,
+        allow_transpositions: bool,
+    ) -> Myers<B> {
+        let column = Column::anchored(pattern_length);
+        Myers::with_column(column, max_distance, allow_transpositions)
+    }
+
+    fn with_column(column: Column<B>, max_distance: usize, allow_transpositions: bool) -> Myers<B> {
+        Myers {
+            column,
+            max_distance,
+            allow_transpositions,
+            // Nothing can be transposed with the character before the text.
+            prev_eq: B::zero(),
+            prev_d0: !B::zero(),
+        }
+    }
+}
+
+impl<B: BitVector> Step<B> for Myers<B> {
+    type Output = usize;
+
+    #[inline]
+    fn step(&mut self, mask: B, _can_start: bool) -> Option<usize> {
+        // Masks have a zero wherever the pattern accepts the character, and
+        // the algorithm wants the opposite.
+        let eq = !mask;
+        let mut d0 = self.column.diagonal(eq);
+        if self.allow_transpositions {
+            // A pair of pattern characters swapped in the text makes the cell
+            // two rows up and two columns back reachable with a single edit,
+            // as long as the diagonal cell in between went up by one.
+            d0 = d0 | (((!self.prev_d0 & eq) << 1) & self.prev_eq);
+            self.prev_eq = eq;
+            self.prev_d0 = d0;
+        }
+        let distance = self.column.advance(d0);
+        if distance <= self.max_distance {
+            return Some(distance);
+        }
+        None
+    }
+}
+
+/// The current column of the edit distance table between the pattern and
+/// the text, where row `i` holds the fewest edits needed to match the first
+/// `i` characters of the pattern ending at the current character.
+///
+/// Neighbouring rows differ by at most one, so the column is stored as two
+/// bit vectors: bit `i` of `vp` is set where row `i + 1` is one more than row
+/// `i`, and bit `i` of `vn` where it's one less.
+pub(crate) struct Column<B> {
+    vp: B,
+    vn: B,
+    /// The value of the last row, which is the distance of the best match
+    /// ending here.
+    distance: usize,
+    last_row: B,
+    /// How much the row above the pattern goes up by with each character.
+    top: B,
+}
+
+impl<B: BitVector> Column<B> {
+    /// Returns the column before any text has been seen, where matching the
+    /// first `i` characters of the pattern takes `i` deletions.
+    pub(crate) fn new(pattern_length: usize) -> Column<B> {
+        Column {
+            vp: !B::zero(),
+            vn: B::zero(),
+            distance: pattern_length,
+            last_row: B::one() << (pattern_length - 1),
+            top: B::zero(),
+        }
+    }
+
+    /// Like `new`, but for a match that has to start at the first character
+    /// of the text, so every character before the current one has to be
+    /// inserted and the row above the pattern goes up by one each time.
+    pub(crate) fn anchored(pattern_length: usize) -> Column<B> {
+        Column {
+            top: B::one(),
+            ..Column::new(pattern_length)
+        }
+    }
+
+    /// Returns the rows of the next column that are the same as the row
+    /// diagonally before them, given the rows of the pattern that accept the
+    /// next character of the text.
+    #[inline]
+    pub(crate) fn diagonal(&self, eq: B) -> B {
+        ((eq & self.vp).wrapping_add(self.vp) ^ self.vp) | eq | self.vn
+    }
+
+    /// Moves on to the next column, returning its last row.
+    #[inline]
+    pub(crate) fn advance(&mut self, d0: B) -> usize {
+        let hp = self.vn | !(d0 | self.vp);
+        let hn = self.vp & d0;
+        if B::zero() != (hp & self.last_row) {
+            self.distance += 1;
+        } else if B::zero() != (hn & self.last_row) {
+            self.distance -= 1;
+        }
+        // When a match can start anywhere in the text, the row above the
+        // pattern is always zero and nothing shifts into the bottom.
+        let hp = (hp << 1) | self.top;
+        let hn = hn << 1;
+        self.vp = hn | !(d0 | hp);
+        self.vn = d0 & hp;
+        self.distance
+    }
+}
@@ -0,0 +1,107 @@
+use std::io::{self, Read};
+use std::str;
+use std::vec;
+
+use crate::{BitVector, Searcher, TextMatch};
+
+/// Iterator that searches everything read from a reader, by feeding it to a
+/// `Searcher` one buffer at a time.
+pub(crate) struct ReaderMatches<'a, R, W> {
+    searcher: Searcher<'a, W>,
+    reader: R,
+    buffer: Box<[u8]>,
+    /// The number of bytes at the start of the buffer left over from the last
+    /// read, because they're the start of a character that got cut off.
+    leftover: usize,
+    pending: vec::IntoIter<TextMatch>,
+    /// An error to return once the matches before it have been.
+    error: Option<io::Error>,
+    done: bool,
+}
+
+impl<'a, R: Read, W: BitVector> ReaderMatches<'a, R, W> {
+    pub(crate) fn new(searcher: Searcher<'a, W>, reader: R) -> ReaderMatches<'a, R, W> {
+        ReaderMatches {
+            searcher,
+            reader,
+            buffer: vec![0; 8 * 1024].into_boxed_slice(),
+            leftover: 0,
+            pending: Vec::new().into_iter(),
+            error: None,
+            done: false,
+        }
+    }
+
+    /// Reads and searches the next buffer full of text. Reading stops at the
+    /// first error.
+    fn fill(&mut self) {
+        let read = loop {
+            match self.reader.read(&mut self.buffer[self.leftover..]) {
+                Ok(read) => break read,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => {
+                    self.fail(e);
+                    return;
+                }
+            }
+        };
+        if read == 0 {
+            self.done = true;
+            // Whatever the searcher held back can end at the end of the text.
+            let held = self.searcher.finish().into_iter().collect::<Vec<_>>();
+            self.pending = held.into_iter();
+            if self.leftover > 0 {
+                self.fail(invalid_utf8());
+            }
+            return;
+        }
+        let filled = self.leftover + read;
+        let valid = match str::from_utf8(&self.buffer[..filled]) {
+            Ok(text) => text.len(),
+            // A character cut off at the end of the buffer will be finished
+            // by the next read, but anything else is just invalid. Either way
+            // the text before it still gets searched.
+            Err(e) => {
+                if e.error_len().is_some() {
+                    self.fail(invalid_utf8());
+                }
+                e.valid_up_to()
+            }
+        };
+        let text = str::from_utf8(&self.buffer[..valid]).unwrap();
+        self.pending = self.searcher.feed(text).into_iter();
+        self.buffer.copy_within(valid..filled, 0);
+        self.leftover = filled - valid;
+    }
+
+    fn fail(&mut self, e: io::Error) {
+        self.error = Some(e);
+        self.done = true;
+    }
+}
+
+impl<'a, R: Read, W: BitVector> Iterator for ReaderMatches<'a, R, W> {
+    type Item = io::Result<TextMatch>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(m) = self.pending.next() {
+                return Some(Ok(m));
+            }
+            if let Some(e) = self.error.take() {
+                return Some(Err(e));
+            }
+            if self.done {
+                return None;
+            }
+            self.fill();
+        }
+    }
+}
+
+fn invalid_utf8() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        "stream did not contain valid UTF-8",
+    )
+}
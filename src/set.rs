@@ -0,0 +1,308 @@
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cmp;
+use core::str::CharIndices;
+
+use crate::class::CharClass;
+use crate::masks::CharMasks;
+use crate::{check_length, length_is_valid, BitVector, Error, MatchSpan, TextMatch, Wide};
+
+/// A set of compiled patterns that can all be searched for in a single pass
+/// over the text.
+///
+/// Patterns are packed side by side into as few bit vectors as possible (the
+/// same trick agrep uses), so searching for a lot of short patterns costs
+/// about as much as searching for one. Patterns that fit in a word share
+/// `usize` masks, and longer ones share `Wide` masks.
+///
+/// ```
+/// use bitap::{PatternSet, TextMatch};
+/// let set = PatternSet::new(&["hello", "world"])?;
+/// let mut matches = set.lev("wxrld hello", 1);
+/// assert_eq!(matches.next(), Some((1, TextMatch{ distance: 1, end: 4, byte_end: 5 })));
+/// assert_eq!(matches.next(), Some((0, TextMatch{ distance: 1, end: 9, byte_end: 10 })));
+/// assert_eq!(matches.next(), Some((0, TextMatch{ distance: 0, end: 10, byte_end: 11 })));
+/// # Ok::<(), bitap::Error>(())
+/// ```
+pub struct PatternSet {
+    lengths: Vec<usize>,
+    words: Vec<Group<usize>>,
+    wide: Vec<Group<Wide<4>>>,
+}
+
+/// Several patterns packed into one bit vector.
+///
+/// Each pattern gets its own run of bits, followed by a separator bit that
+/// doubles as its match bit. Every step, the state bit at the start of each
+/// pattern is cleared so that nothing carries over from the pattern before
+/// it, and otherwise the patterns are searched exactly like a single one.
+struct Group<B> {
+    masks: CharMasks<B>,
+    /// The index of each pattern in the set, along with its offset and
+    /// length within the group.
+    patterns: Vec<(usize, usize, usize)>,
+    starts: B,
+    match_bits: B,
+}
+
+impl<B: BitVector> Group<B> {
+    fn new(patterns: Vec<(usize, &str)>) -> Group<B> {
+        let mut classes = Vec::new();
+        let mut layout = Vec::new();
+        let mut starts = B::zero();
+        let mut match_bits = B::zero();
+        for (index, pattern) in patterns {
+            let offset = classes.len();
+            classes.extend(pattern.chars().map(CharClass::Char));
+            let length = classes.len() - offset;
+            // A separator that accepts nothing.
+            classes.push(CharClass::Set(Vec::new()));
+            layout.push((index, offset, length));
+            starts = starts | (B::one() << offset);
+            match_bits = match_bits | (B::one() << (offset + length));
+        }
+        Group {
+            masks: CharMasks::new(&classes, false, &[]),
+            patterns: layout,
+            starts,
+            match_bits,
+        }
+    }
+
+    /// Returns the initial state for an error level, where the first `level`
+    /// characters of every pattern can be deleted.
+    fn initial(&self, level: usize) -> B {
+        let mut r = !B::zero();
+        for &(_, offset, length) in &self.patterns {
+            for i in 0..=cmp::min(level, length) {
+                r = r & !(B::one() << (offset + i));
+            }
+        }
+        r
+    }
+
+    fn state(&self, max_distance: usize) -> State<B> {
+        State {
+            r: (0..=max_distance).map(|i| self.initial(i)).collect(),
+            t: vec![!self.starts; max_distance],
+        }
+    }
+
+    /// Advances the search by one character, pushing any matches that end on
+    /// it. `byte_end` is the byte offset just past the character.
+    fn step(
+        &self,
+        state: &mut State<B>,
+        c: char,
+        (end, byte_end): (usize, usize),
+        allow_transpositions: bool,
+        matches: &mut Vec<(usize, TextMatch)>,
+    ) {
+        let mask = self.masks.get(c);
+        let keep = !self.starts;
+        let r = &mut state.r;
+        let t = &mut state.t;
+        let mut prev_parent = r[0];
+        r[0] = ((r[0] | mask) << 1) & keep;
+        for j in 1..r.len() {
+            let prev = r[j];
+            let current = (prev | mask) << 1;
+            let replace = prev_parent << 1;
+            let delete = r[j - 1] << 1;
+            let insert = prev_parent;
+            let mut next = current & insert & delete & replace;
+            if allow_transpositions {
+                let transpose = (t[j - 1] | (mask << 1)) << 1;
+                next = next & transpose;
+                t[j - 1] = ((prev_parent << 1) & keep) | mask;
+            }
+            r[j] = next & keep;
+            prev_parent = prev;
+        }
+
+        // Anything that matched at all matched at the highest level.
+        if self.match_bits == (r[r.len() - 1] & self.match_bits) {
+            return;
+        }
+        for &(index, offset, length) in &self.patterns {
+            let match_bit = B::one() << (offset + length);
+            if let Some(distance) = r.iter().position(|&rv| B::zero() == (rv & match_bit)) {
+                let m = TextMatch {
+                    distance,
+                    end,
+                    byte_end,
+                };
+                matches.push((index, m));
+            }
+        }
+    }
+}
+
+struct State<B> {
+    r: Vec<B>,
+    t: Vec<B>,
+}
+
+impl PatternSet {
+    /// Compiles and returns a new set from the passed patterns. Will fail if
+    /// any of the patterns is empty or longer than `MAX_PATTERN_LENGTH`.
+    pub fn new<I, S>(patterns: I) -> Result<PatternSet, Error>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let patterns = patterns.into_iter().collect::<Vec<_>>();
+        let lengths = patterns
+            .iter()
+            .map(|p| p.as_ref().chars().count())
+            .collect::<Vec<_>>();
+        for &len in &lengths {
+            check_length::<Wide<4>>(len)?;
+        }
+        let (short, long): (Vec<_>, Vec<_>) = patterns
+            .iter()
+            .map(|p| p.as_ref())
+            .enumerate()
+            .partition(|&(i, _)| length_is_valid::<usize>(lengths[i]));
+        Ok(PatternSet {
+            words: pack(short, &lengths),
+            wide: pack(long, &lengths),
+            lengths,
+        })
+    }
+
+    /// Returns the number of patterns in the set.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.lengths.len()
+    }
+
+    /// Returns whether the set has no patterns in it.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.lengths.is_empty()
+    }
+
+    /// Returns an iterator of `(pattern_index, span)` pairs for every place a
+    /// pattern can be found exactly within the passed text. Pairs are ordered
+    /// by where the match ends, and then by pattern index.
+    pub fn find<'a>(&'a self, text: &'a str) -> impl Iterator<Item = (usize, MatchSpan)> + 'a {
+        SetMatches::new(self, text, 0, false).map(move |(i, m)| {
+            // Exact matches are always as long as the pattern, so the start is
+            // only ever that many characters back.
+            let length = self.lengths[i];
+            let (byte_start, _) = text[..m.byte_end]
+                .char_indices()
+                .rev()
+                .nth(length - 1)
+                .unwrap();
+            let span = MatchSpan {
+                distance: 0,
+                start: m.end + 1 - length,
+                end: m.end,
+                byte_start,
+                byte_end: m.byte_end,
+            };
+            (i, span)
+        })
+    }
+
+    /// Returns an iterator of `(pattern_index, match)` pairs for every place
+    /// a pattern matched the passed text within a levenshtein distance of
+    /// `max_distance`. Pairs are ordered by where the match ends, and then by
+    /// pattern index.
+    pub fn lev<'a>(
+        &'a self,
+        text: &'a str,
+        max_distance: usize,
+    ) -> impl Iterator<Item = (usize, TextMatch)> + 'a {
+        SetMatches::new(self, text, max_distance, false)
+    }
+
+    /// Returns an iterator of `(pattern_index, match)` pairs for every place
+    /// a pattern matched the passed text within an optimal string alignment
+    /// distance of `max_distance`.
+    pub fn osa<'a>(
+        &'a self,
+        text: &'a str,
+        max_distance: usize,
+    ) -> impl Iterator<Item = (usize, TextMatch)> + 'a {
+        SetMatches::new(self, text, max_distance, true)
+    }
+}
+
+/// Packs patterns into groups in order, starting a new group whenever the
+/// next pattern doesn't fit.
+fn pack<B: BitVector>(patterns: Vec<(usize, &str)>, lengths: &[usize]) -> Vec<Group<B>> {
+    let mut groups = Vec::new();
+    let mut group = Vec::new();
+    let mut bits = 0;
+    for (index, pattern) in patterns {
+        // Every pattern needs a bit per character plus its match bit.
+        let needed = lengths[index] + 1;
+        if bits + needed > B::BITS {
+            groups.push(Group::new(group));
+            group = Vec::new();
+            bits = 0;
+        }
+        group.push((index, pattern));
+        bits += needed;
+    }
+    if !group.is_empty() {
+        groups.push(Group::new(group));
+    }
+    groups
+}
+
+struct SetMatches<'a> {
+    set: &'a PatternSet,
+    chars: CharIndices<'a>,
+    position: usize,
+    words: Vec<State<usize>>,
+    wide: Vec<State<Wide<4>>>,
+    allow_transpositions: bool,
+    /// Matches ending on the current character, in reverse order.
+    pending: Vec<(usize, TextMatch)>,
+}
+
+impl<'a> SetMatches<'a> {
+    fn new(
+        set: &'a PatternSet,
+        text: &'a str,
+        max_distance: usize,
+        allow_transpositions: bool,
+    ) -> SetMatches<'a> {
+        let longest = set.lengths.iter().cloned().max().unwrap_or(0);
+        let max_distance = cmp::min(max_distance, longest);
+        SetMatches {
+            set,
+            chars: text.char_indices(),
+            position: 0,
+            words: set.words.iter().map(|g| g.state(max_distance)).collect(),
+            wide: set.wide.iter().map(|g| g.state(max_distance)).collect(),
+            allow_transpositions,
+            pending: Vec::new(),
+        }
+    }
+}
+
+impl<'a> Iterator for SetMatches<'a> {
+    type Item = (usize, TextMatch);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.pending.is_empty() {
+            let (offset, c) = self.chars.next()?;
+            let end = (self.position, offset + c.len_utf8());
+            let transpose = self.allow_transpositions;
+            for (group, state) in self.set.words.iter().zip(&mut self.words) {
+                group.step(state, c, end, transpose, &mut self.pending);
+            }
+            for (group, state) in self.set.wide.iter().zip(&mut self.wide) {
+                group.step(state, c, end, transpose, &mut self.pending);
+            }
+            self.pending.sort_by_key(|&(index, _)| cmp::Reverse(index));
+            self.position += 1;
+        }
+        self.pending.pop()
+    }
+}
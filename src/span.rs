@@ -0,0 +1,142 @@
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cmp;
+
+use crate::bounds::Bounds;
+use crate::step::transposition_start;
+use crate::{mask_iter, BitVector, Masks, Pattern, TextMatch};
+
+/// MatchSpan is a match with its start position recovered, so it covers the
+/// whole range of matching text.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct MatchSpan {
+    /// The edit distance for this match.
+    pub distance: usize,
+    /// The index of the first character of the match.
+    pub start: usize,
+    /// The index of the last character of the match.
+    pub end: usize,
+    /// The byte offset of the first character of the match.
+    pub byte_start: usize,
+    /// The byte offset just past the last character of the match, so the
+    /// matching text is `&text[byte_start..byte_end]`.
+    pub byte_end: usize,
+}
+
+/// Iterator adapter that recovers the start position of each match.
+///
+/// Bitap can't tell you where a match starts, but once you know where it ends
+/// and what its distance is, running bitap _backwards_ from the end with the
+/// reversed pattern can. The reverse pass only needs to look at the
+/// `pattern_length + distance` characters before the end of the match, so
+/// this is cheap as long as matches are relatively rare. If the pattern only
+/// allows matches to start in certain places, only alignments that start in
+/// one of them are considered.
+pub(crate) struct Spans<'a, I, W> {
+    matches: I,
+    text: &'a str,
+    reversed: Pattern<W>,
+    allow_transpositions: bool,
+    bounds: Bounds,
+}
+
+impl<'a, I, W> Spans<'a, I, W> {
+    pub(crate) fn new(
+        matches: I,
+        text: &'a str,
+        reversed: Pattern<W>,
+        allow_transpositions: bool,
+        bounds: Bounds,
+    ) -> Spans<'a, I, W> {
+        Spans {
+            matches,
+            text,
+            reversed,
+            allow_transpositions,
+            bounds,
+        }
+    }
+}
+
+impl<'a, I: Iterator<Item = TextMatch>, W: BitVector> Iterator for Spans<'a, I, W> {
+    type Item = MatchSpan;
+
+    fn next(&mut self) -> Option<MatchSpan> {
+        let m = self.matches.next()?;
+        let before = &self.text[..m.byte_end];
+        let rev = before.chars().rev();
+
+        let len = self.reversed.len();
+        let (distance, transpose) = (m.distance, self.allow_transpositions);
+        let bounds = self.bounds;
+        let can_start = |length: usize| bounds.can_start_after(before.chars().rev().nth(length));
+        let length = match &self.reversed.masks {
+            Masks::Word(masks) => {
+                alignment_length(mask_iter(masks, rev), len, distance, transpose, can_start)
+            }
+            Masks::Wide2(masks) => {
+                alignment_length(mask_iter(masks, rev), len, distance, transpose, can_start)
+            }
+            Masks::Wide4(masks) => {
+                alignment_length(mask_iter(masks, rev), len, distance, transpose, can_start)
+            }
+        };
+        let (byte_start, _) = before.char_indices().rev().nth(length - 1)?;
+        Some(MatchSpan {
+            distance: m.distance,
+            start: m.end + 1 - length,
+            end: m.end,
+            byte_start,
+            byte_end: m.byte_end,
+        })
+    }
+}
+
+/// Returns the length of the longest alignment of the pattern within
+/// `distance` edits that starts at the very first mask, out of the lengths
+/// that `can_end` allows.
+///
+/// This is the same as the regular bitap loop, except that where bitap shifts
+/// in zeros (meaning "the empty prefix of the pattern matches here"), this
+/// shifts in ones so that matches can't start anywhere but the beginning.
+/// Given the reversed pattern and the text before a match in reverse, that
+/// gives us the distance back to the leftmost start of the match.
+fn alignment_length<B: BitVector, I: Iterator<Item = B>, F: Fn(usize) -> bool>(
+    mask_iter: I,
+    pattern_length: usize,
+    distance: usize,
+    allow_transpositions: bool,
+    can_end: F,
+) -> usize {
+    let match_bit = B::one() << pattern_length;
+    let mut r: Vec<B> = (0..=distance)
+        .map(|i| !B::one() << cmp::min(i, pattern_length))
+        .collect();
+    let mut t = vec![!B::one(); distance];
+
+    // No alignment can be longer than the pattern plus the number of
+    // insertions, so there's no need to look any further back than that.
+    let mut length = 0;
+    for (i, mask) in mask_iter.take(pattern_length + distance).enumerate() {
+        let mut prev_parent = r[0];
+        r[0] = ((r[0] | mask) << 1) | B::one();
+        for j in 1..r.len() {
+            let prev = r[j];
+            let current = ((prev | mask) << 1) | B::one();
+            let replace = (prev_parent << 1) | B::one();
+            let delete = (r[j - 1] << 1) | B::one();
+            let insert = prev_parent;
+            r[j] = current & insert & delete & replace;
+            if allow_transpositions {
+                let transpose = ((t[j - 1] | (mask << 1)) << 1) | B::one();
+                r[j] = r[j] & transpose;
+                t[j - 1] = transposition_start(prev_parent, mask, 0);
+            }
+            prev_parent = prev;
+        }
+        if B::zero() == (r[distance] & match_bit) && can_end(i + 1) {
+            length = i + 1;
+        }
+    }
+    length
+}
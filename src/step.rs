@@ -0,0 +1,546 @@
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cmp;
+
+use crate::BitVector;
+
+/// The state of a search, advanced one character of the text at a time.
+///
+/// Each of the iterator adapters is one of these fed from an iterator of
+/// masks, and `Pattern` feeds the same ones from its text with
+/// `BoundedMatches`, which also says where a match can start.
+///
+/// Bitap normally shifts a zero into the bottom of every state vector,
+/// meaning that the empty prefix of the pattern matches right before the
+/// next character, so a match can start anywhere. The searches that allow
+/// deletions only keep alignments that cover at least one character of the
+/// text instead, and add the ones starting at a character right before it's
+/// read, if a match can start there: the first `j` characters of the pattern
+/// match at level `j` by deleting them. Otherwise, an alignment that starts
+/// after the last character and deletes the whole pattern would count as a
+/// match, even though it's empty.
+pub(crate) trait Step<B> {
+    /// What the search returns for a match, usually its distance.
+    type Output;
+
+    /// Moves the search past the next character of the text, given its mask
+    /// and whether a match can start at it, and returns the match that ends
+    /// on it, if there is one.
+    fn step(&mut self, mask: B, can_start: bool) -> Option<Self::Output>;
+}
+
+/// Returns the state a level of a search starts out in.
+///
+/// When a match can start at every character, the state can include the
+/// alignments that start at the next one ahead of time, like plain bitap:
+/// the first `level` characters of the pattern are matched by deleting them.
+/// Other than that, it's what `starting` adds before every step, so it saves
+/// adding it over and over.
+#[inline]
+fn initial<B: BitVector>(level: usize, starts_anywhere: bool) -> B {
+    if starts_anywhere {
+        !B::one() << level
+    } else {
+        !B::zero()
+    }
+}
+
+/// Returns a level of a search's state with the alignments that start at
+/// the current character added, if a match can start there. For level `j`,
+/// `start` has its lowest `j + 1` bits clear.
+#[inline]
+fn starting<B: BitVector>(r: B, start: B, can_start: bool) -> B {
+    if can_start {
+        r & start
+    } else {
+        r
+    }
+}
+
+/// Returns whether `starting` has anything to add before a step, and what
+/// the bottom bit of each level is before an insertion can clear it. The
+/// bottom bit is the empty prefix, which only ever matches right before the
+/// next character in a state that already includes the alignments that
+/// start there, since those don't need adding.
+#[inline]
+fn empty_prefix<B: BitVector, const ANYWHERE: bool>(can_start: bool) -> (bool, B) {
+    if ANYWHERE {
+        (false, B::zero())
+    } else {
+        (can_start, B::one())
+    }
+}
+
+/// Returns the transpositions that the current character starts, given the
+/// state of the level below from before it was read.
+///
+/// Bit `i` is clear wherever the current character is accepted by character
+/// `i` of the pattern, and the `i - deleted - 1` characters before that
+/// matched right before it, so that the transposition finishes if the next
+/// character is accepted by character `i - deleted - 1`. Any `deleted`
+/// characters between the two are deleted. The lowest `deleted + 1` bits
+/// would transpose "nothing" with a character of the pattern, which is just
+/// an insertion in disguise when a match can start anywhere, but not when it
+/// can't, so those are never allowed.
+#[inline]
+pub(crate) fn transposition_start<B: BitVector>(before: B, mask: B, deleted: usize) -> B {
+    (before << (deleted + 1)) | mask | !(!B::zero() << (deleted + 1))
+}
+
+/// Adds levels to a search past the length of the pattern, as matches come
+/// to need them.
+///
+/// While a match can start anywhere, none needs more edits than there are
+/// characters in the pattern, since its last character alone is that close.
+/// When it can only start in some places, it can need one for every
+/// character since the last of those instead. A new level starts out with
+/// every prefix of the pattern matched, since lining it up with the text
+/// since then takes at most one edit per character of either.
+struct Growth<B> {
+    max_distance: usize,
+    /// The number of characters since a match could last start, counting
+    /// the one it can start at.
+    since_start: usize,
+    matched: B,
+}
+
+impl<B: BitVector> Growth<B> {
+    fn new(pattern_length: usize, max_distance: usize) -> Growth<B> {
+        Growth {
+            max_distance,
+            since_start: 0,
+            matched: !B::one() << pattern_length,
+        }
+    }
+
+    /// Returns the state for a new level if a search with `levels` of them
+    /// needs another one before reading the next character.
+    #[inline]
+    fn next_level(&mut self, levels: usize, can_start: bool) -> Option<B> {
+        // Levels are never taken away, so once there are enough of them,
+        // nothing else needs tracking.
+        if levels > self.max_distance {
+            return None;
+        }
+        if can_start {
+            self.since_start = 0;
+        }
+        self.since_start += 1;
+        if levels <= self.since_start {
+            return Some(self.matched);
+        }
+        None
+    }
+}
+
+/// The state of `find`. It can't delete anything, so its state doesn't need
+/// to tell empty alignments apart: bit zero is the empty prefix, which only
+/// matches where a match can start.
+pub(crate) struct Exact<B> {
+    r: B,
+    match_bit: B,
+}
+
+impl<B: BitVector> Exact<B> {
+    pub(crate) fn new(pattern_length: usize) -> Exact<B> {
+        Exact {
+            r: !B::one(),
+            match_bit: B::one() << pattern_length,
+        }
+    }
+}
+
+impl<B: BitVector> Step<B> for Exact<B> {
+    type Output = usize;
+
+    #[inline]
+    fn step(&mut self, mask: B, can_start: bool) -> Option<usize> {
+        let prev = if can_start { self.r } else { self.r | B::one() };
+        self.r = (prev | mask) << 1;
+        if B::zero() == (self.r & self.match_bit) {
+            return Some(0);
+        }
+        None
+    }
+}
+
+/// The state of `levenshtein` and `optimal_string_alignment`: a state
+/// vector for every number of edits up to the max distance.
+pub(crate) struct Levels<B> {
+    r: Vec<B>,
+    t: Vec<B>,
+    match_bit: B,
+    allow_transpositions: bool,
+    growth: Growth<B>,
+    /// Whether the state already includes the alignments that start at the
+    /// next character. See `initial`.
+    starts_anywhere: bool,
+}
+
+impl<B: BitVector> Levels<B> {
+    pub(crate) fn new(
+        pattern_length: usize,
+        max_distance: usize,
+        allow_transpositions: bool,
+        starts_anywhere: bool,
+    ) -> Levels<B> {
+        let levels = cmp::min(max_distance, pattern_length) + 1;
+        Levels {
+            r: (0..levels).map(|j| initial(j, starts_anywhere)).collect(),
+            t: vec![!B::zero(); levels - 1],
+            match_bit: B::one() << pattern_length,
+            allow_transpositions,
+            growth: Growth::new(pattern_length, max_distance),
+            starts_anywhere,
+        }
+    }
+
+    /// Moves every level past the next character, without looking for a
+    /// match.
+    #[inline]
+    fn advance(&mut self, mask: B, can_start: bool) {
+        if self.starts_anywhere {
+            self.advance_from::<true>(mask, can_start)
+        } else {
+            self.advance_from::<false>(mask, can_start)
+        }
+    }
+
+    /// `advance`, with whether the state includes the alignments that start
+    /// at the next character known at compile time, so the search doesn't
+    /// pay for adding them when it doesn't need to.
+    #[inline]
+    fn advance_from<const ANYWHERE: bool>(&mut self, mask: B, can_start: bool) {
+        // A match that can start anywhere never needs more levels than the
+        // search starts out with.
+        if !ANYWHERE {
+            if let Some(level) = self.growth.next_level(self.r.len(), can_start) {
+                self.r.push(level);
+                self.t.push(!B::zero());
+            }
+        }
+        let (can_start, empty) = empty_prefix::<B, ANYWHERE>(can_start);
+        let r = &mut self.r;
+        let t = &mut self.t;
+        let mut start = !B::one();
+        let mut prev_parent = starting(r[0], start, can_start);
+        r[0] = ((prev_parent | mask) << 1) | empty;
+        for j in 1..r.len() {
+            start = start << 1;
+            let prev = starting(r[j], start, can_start);
+            let current = (prev | mask) << 1;
+            let replace = prev_parent << 1;
+            let delete = r[j - 1] << 1;
+            let insert = prev_parent;
+            let mut next = current & delete & replace;
+            if self.allow_transpositions {
+                let transpose = (t[j - 1] | (mask << 1)) << 1;
+                next = next & transpose;
+                t[j - 1] = transposition_start(prev_parent, mask, 0);
+            }
+            // Inserting the current character is the only way for an
+            // alignment that starts at it to cover nothing else.
+            r[j] = (next | empty) & insert;
+            prev_parent = prev;
+        }
+    }
+}
+
+impl<B: BitVector> Step<B> for Levels<B> {
+    type Output = usize;
+
+    #[inline]
+    fn step(&mut self, mask: B, can_start: bool) -> Option<usize> {
+        self.advance(mask, can_start);
+        let match_bit = self.match_bit;
+        self.r.iter().position(|&rv| B::zero() == (rv & match_bit))
+    }
+}
+
+/// The state of `levenshtein_k` and `optimal_string_alignment_k`.
+pub(crate) struct Fixed<B, const K: usize> {
+    // Level zero has no parent, so it's kept apart from the other K levels.
+    r0: B,
+    r: [B; K],
+    t: [B; K],
+    match_bit: B,
+    allow_transpositions: bool,
+    /// The same as for `Levels`.
+    starts_anywhere: bool,
+}
+
+impl<B: BitVector, const K: usize> Fixed<B, K> {
+    pub(crate) fn new(
+        pattern_length: usize,
+        allow_transpositions: bool,
+        starts_anywhere: bool,
+    ) -> Fixed<B, K> {
+        let mut r = [!B::zero(); K];
+        for (j, rv) in r.iter_mut().enumerate() {
+            *rv = initial(j + 1, starts_anywhere);
+        }
+        Fixed {
+            r0: initial(0, starts_anywhere),
+            r,
+            t: [!B::zero(); K],
+            match_bit: B::one() << pattern_length,
+            allow_transpositions,
+            starts_anywhere,
+        }
+    }
+
+    /// The same as `Levels::advance_from`.
+    #[inline]
+    fn step_from<const ANYWHERE: bool>(&mut self, mask: B, can_start: bool) -> Option<usize> {
+        let (can_start, empty) = empty_prefix::<B, ANYWHERE>(can_start);
+        let mut start = !B::one();
+        let mut prev_parent = starting(self.r0, start, can_start);
+        self.r0 = ((prev_parent | mask) << 1) | empty;
+        let mut parent = self.r0;
+        for (rv, tv) in self.r.iter_mut().zip(self.t.iter_mut()) {
+            start = start << 1;
+            let prev = starting(*rv, start, can_start);
+            let current = (prev | mask) << 1;
+            let replace = prev_parent << 1;
+            let delete = parent << 1;
+            let insert = prev_parent;
+            let mut next = current & delete & replace;
+            if self.allow_transpositions {
+                let transpose = (*tv | (mask << 1)) << 1;
+                next = next & transpose;
+                *tv = transposition_start(prev_parent, mask, 0);
+            }
+            *rv = (next | empty) & insert;
+            parent = *rv;
+            prev_parent = prev;
+        }
+        distance_k(self.r0, &self.r, self.match_bit)
+    }
+}
+
+impl<B: BitVector, const K: usize> Step<B> for Fixed<B, K> {
+    type Output = usize;
+
+    #[inline]
+    fn step(&mut self, mask: B, can_start: bool) -> Option<usize> {
+        if self.starts_anywhere {
+            self.step_from::<true>(mask, can_start)
+        } else {
+            self.step_from::<false>(mask, can_start)
+        }
+    }
+}
+
+/// Returns the lowest level that matched, if any did.
+#[inline]
+fn distance_k<const K: usize, B: BitVector>(r0: B, r: &[B; K], match_bit: B) -> Option<usize> {
+    if B::zero() == (r0 & match_bit) {
+        return Some(0);
+    }
+    r.iter()
+        .position(|&rv| B::zero() == (rv & match_bit))
+        .map(|j| j + 1)
+}
+
+/// The state of `hamming` and `hamming_static`, which only keep levels for
+/// substitutions. `R` is where the levels are stored, which is a fixed size
+/// array for `hamming_static`. Like `Exact`, bit zero of every level is the
+/// empty prefix.
+pub(crate) struct Hamming<B, R = Vec<B>> {
+    r: R,
+    levels: usize,
+    match_bit: B,
+}
+
+impl<B: BitVector> Hamming<B> {
+    pub(crate) fn new(pattern_length: usize, max_distance: usize) -> Hamming<B> {
+        let max_distance = cmp::min(max_distance, pattern_length);
+        Hamming::with_levels(
+            vec![!B::one(); max_distance + 1],
+            pattern_length,
+            max_distance,
+        )
+    }
+}
+
+impl<B: BitVector> Hamming<B, [B; 3]> {
+    /// Returns the state for a max distance of at most two, without
+    /// allocating.
+    pub(crate) fn new_static(pattern_length: usize, max_distance: usize) -> Hamming<B, [B; 3]> {
+        Hamming::with_levels([!B::one(); 3], pattern_length, max_distance)
+    }
+}
+
+impl<B: BitVector, R> Hamming<B, R> {
+    fn with_levels(r: R, pattern_length: usize, max_distance: usize) -> Hamming<B, R> {
+        Hamming {
+            r,
+            levels: cmp::min(max_distance, pattern_length) + 1,
+            match_bit: B::one() << pattern_length,
+        }
+    }
+}
+
+impl<B: BitVector, R: AsMut<[B]>> Step<B> for Hamming<B, R> {
+    type Output = usize;
+
+    #[inline]
+    fn step(&mut self, mask: B, can_start: bool) -> Option<usize> {
+        let r = &mut self.r.as_mut()[..self.levels];
+        let start = if can_start { B::zero() } else { B::one() };
+        let mut prev_parent = r[0] | start;
+        r[0] = (prev_parent | mask) << 1;
+        for rv in r.iter_mut().skip(1) {
+            let prev = *rv | start;
+            let current = (prev | mask) << 1;
+            let replace = prev_parent << 1;
+            *rv = current & replace;
+            prev_parent = prev;
+        }
+        let match_bit = self.match_bit;
+        r.iter().position(|&rv| B::zero() == (rv & match_bit))
+    }
+}
+
+/// The state of `damerau_levenshtein`.
+pub(crate) struct Damerau<B> {
+    r: Vec<B>,
+    prev: Vec<B>,
+    // Transpositions waiting on their second character, by the error level
+    // they'll finish at. Text between the swapped characters is inserted.
+    inserted: Vec<B>,
+    // The same, but with `gap + 1` pattern characters deleted between the
+    // swapped characters. A transposition never has both.
+    deleted: Vec<Vec<B>>,
+    match_bit: B,
+    growth: Growth<B>,
+}
+
+impl<B: BitVector> Damerau<B> {
+    pub(crate) fn new(pattern_length: usize, max_distance: usize) -> Damerau<B> {
+        let levels = cmp::min(max_distance, pattern_length) + 1;
+        Damerau {
+            r: vec![!B::zero(); levels],
+            prev: vec![!B::zero(); levels],
+            inserted: vec![!B::zero(); levels],
+            deleted: vec![vec![!B::zero(); levels]; levels.saturating_sub(2)],
+            match_bit: B::one() << pattern_length,
+            growth: Growth::new(pattern_length, max_distance),
+        }
+    }
+
+    /// Adds another level. Nothing is waiting to finish at it yet, and
+    /// there's one more gap a transposition can have at the top.
+    fn grow(&mut self, level: B) {
+        self.r.push(level);
+        self.prev.push(level);
+        self.inserted.push(!B::zero());
+        for d in &mut self.deleted {
+            d.push(!B::zero());
+        }
+        if self.r.len() > 2 {
+            self.deleted.push(vec![!B::zero(); self.r.len()]);
+        }
+    }
+}
+
+impl<B: BitVector> Step<B> for Damerau<B> {
+    type Output = usize;
+
+    #[inline]
+    fn step(&mut self, mask: B, can_start: bool) -> Option<usize> {
+        if let Some(level) = self.growth.next_level(self.r.len(), can_start) {
+            self.grow(level);
+        }
+        let r = &mut self.r;
+        let prev = &mut self.prev;
+        let mut start = !B::one();
+        for (p, &rv) in prev.iter_mut().zip(r.iter()) {
+            *p = starting(rv, start, can_start);
+            start = start << 1;
+        }
+        r[0] = ((prev[0] | mask) << 1) | B::one();
+        for j in 1..r.len() {
+            let current = (prev[j] | mask) << 1;
+            let replace = prev[j - 1] << 1;
+            let delete = r[j - 1] << 1;
+            let insert = prev[j - 1];
+            let mut transpose = self.inserted[j] | (mask << 2);
+            for (gap, d) in self.deleted.iter().enumerate().take(j - 1) {
+                transpose = transpose & (d[j] | (mask << (gap + 3)));
+            }
+            r[j] = ((current & delete & replace & transpose) | B::one()) & insert;
+        }
+
+        // Start transpositions whose first character is this one, and keep
+        // waiting on the ones that haven't finished yet. Going backwards
+        // means each level still sees the previous state of the one below.
+        for j in (1..r.len()).rev() {
+            let start = transposition_start(prev[j - 1], mask, 0) << 1;
+            self.inserted[j] = if j > 1 {
+                start & self.inserted[j - 1]
+            } else {
+                start
+            };
+        }
+        for (gap, d) in self.deleted.iter_mut().enumerate() {
+            for j in gap + 2..r.len() {
+                d[j] = transposition_start(prev[j - 2 - gap], mask, gap + 1) << 1;
+            }
+        }
+
+        let match_bit = self.match_bit;
+        r.iter().position(|&rv| B::zero() == (rv & match_bit))
+    }
+}
+
+/// The state of `levenshtein_prefix`, which is the same as `levenshtein`'s
+/// but looks for a match at every prefix of the pattern that's long enough.
+pub(crate) struct Prefix<B> {
+    levels: Levels<B>,
+    // The state bit for every prefix that's long enough to be reported. Bit
+    // `i` is clear wherever the first `i` characters of the pattern matched,
+    // so the usual match bit is just the longest prefix.
+    prefixes: B,
+    pattern_length: usize,
+    min_length: usize,
+}
+
+impl<B: BitVector> Prefix<B> {
+    pub(crate) fn new(pattern_length: usize, max_distance: usize, min_length: usize) -> Prefix<B> {
+        let min_length = cmp::max(min_length, 1);
+        Prefix {
+            // Deleting a prefix isn't matching it, so this can't keep the
+            // alignments that start at the next character around.
+            levels: Levels::new(pattern_length, max_distance, false, false),
+            prefixes: (min_length..=pattern_length).fold(B::zero(), |p, i| p | (B::one() << i)),
+            pattern_length,
+            min_length,
+        }
+    }
+}
+
+impl<B: BitVector> Step<B> for Prefix<B> {
+    /// The length of the prefix that matched and its distance.
+    type Output = (usize, usize);
+
+    #[inline]
+    fn step(&mut self, mask: B, can_start: bool) -> Option<(usize, usize)> {
+        self.levels.advance(mask, can_start);
+        let (r, prefixes) = (&self.levels.r, self.prefixes);
+        // Anything that matched at all matched at the highest level.
+        if prefixes == (r[r.len() - 1] & prefixes) {
+            return None;
+        }
+        for (k, rv) in r.iter().enumerate() {
+            let matched = !*rv & prefixes;
+            if B::zero() != matched {
+                let consumed = (self.min_length..=self.pattern_length)
+                    .rev()
+                    .find(|&len| B::zero() != (matched & (B::one() << len)))?;
+                return Some((consumed, k));
+            }
+        }
+        None
+    }
+}
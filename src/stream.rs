@@ -0,0 +1,152 @@
+use alloc::vec::Vec;
+
+use crate::bounds::Bounds;
+use crate::masks::CharMasks;
+use crate::myers::Myers;
+use crate::step::{Exact, Fixed, Levels, Step};
+use crate::{BitVector, Boundary, ByEngine, Engine, Masks, Pattern, Search, TextMatch, Wide};
+
+/// A search that's fed text a chunk at a time, for input that's too big to
+/// hold in memory all at once or that hasn't all arrived yet.
+///
+/// The searcher keeps the bitap state between chunks, so matches that
+/// straddle a chunk boundary are still found, and however the text is split
+/// up, the matches are exactly the same as searching all of it in one go.
+/// Positions are counted from the start of the first chunk.
+///
+/// ```
+/// use bitap::{Pattern,TextMatch};
+/// let pattern = Pattern::new("wxrld")?;
+/// let mut searcher = pattern.lev_searcher(1);
+/// assert_eq!(searcher.feed("hello wo"), vec![]);
+/// assert_eq!(searcher.feed("rld"), vec![TextMatch{ distance: 1, end: 10, byte_end: 11 }]);
+/// # Ok::<(), bitap::Error>(())
+/// ```
+pub struct Searcher<'a, W = usize> {
+    state: States<'a, W>,
+    cursor: Cursor,
+}
+
+/// The masks of the pattern along with the search state, for whichever
+/// mask representation the pattern uses.
+enum States<'a, W> {
+    Word(&'a CharMasks<W>, Search<W>),
+    Wide2(&'a CharMasks<Wide<2>>, Search<Wide<2>>),
+    Wide4(&'a CharMasks<Wide<4>>, Search<Wide<4>>),
+}
+
+/// How much of the text a searcher has seen so far.
+struct Cursor {
+    bounds: Bounds,
+    /// Whether a match can start at the next character.
+    can_start: bool,
+    /// A match on the last character so far, if it can only be returned once
+    /// the next one shows whether it's allowed to end there.
+    held: Option<TextMatch>,
+    position: usize,
+    byte_offset: usize,
+}
+
+impl<'a, W: BitVector> Searcher<'a, W> {
+    pub(crate) fn new(
+        pattern: &'a Pattern<W>,
+        max_distance: usize,
+        allow_transpositions: bool,
+    ) -> Searcher<'a, W> {
+        let engine = pattern.engine_for(max_distance);
+        let max_distance = pattern.most_edits(max_distance);
+        let search = (pattern.len(), max_distance, allow_transpositions);
+        let start = pattern.bounds.start;
+        let state = match &pattern.masks {
+            Masks::Word(masks) => States::Word(masks, new_search(engine, search, start)),
+            Masks::Wide2(masks) => States::Wide2(masks, new_search(engine, search, start)),
+            Masks::Wide4(masks) => States::Wide4(masks, new_search(engine, search, start)),
+        };
+        let cursor = Cursor {
+            bounds: pattern.bounds,
+            // Every kind of boundary allows a match to start at the start of
+            // the text.
+            can_start: true,
+            held: None,
+            position: 0,
+            byte_offset: 0,
+        };
+        Searcher { state, cursor }
+    }
+
+    /// Searches the next chunk of text, returning every match that ends
+    /// within it.
+    ///
+    /// If the pattern restricts where matches can end, a match on the last
+    /// character of the chunk is returned with the next one instead, or by
+    /// `finish`.
+    pub fn feed(&mut self, chunk: &str) -> Vec<TextMatch> {
+        let mut matches = Vec::new();
+        let cursor = &mut self.cursor;
+        match &mut self.state {
+            States::Word(masks, search) => cursor.feed(masks, search, chunk, &mut matches),
+            States::Wide2(masks, search) => cursor.feed(masks, search, chunk, &mut matches),
+            States::Wide4(masks, search) => cursor.feed(masks, search, chunk, &mut matches),
+        }
+        matches
+    }
+
+    /// Returns the match on the last character of the text, if `feed` held
+    /// one back. Call this once all of the text has been fed.
+    ///
+    /// ```
+    /// use bitap::{Boundary,PatternBuilder,TextMatch};
+    /// let pattern = PatternBuilder::new("cat").match_end(Boundary::Word).build()?;
+    /// let mut searcher = pattern.lev_searcher(0);
+    /// assert_eq!(searcher.feed("a cat"), vec![]);
+    /// assert_eq!(searcher.finish(), Some(TextMatch{ distance: 0, end: 4, byte_end: 5 }));
+    /// # Ok::<(), bitap::Error>(())
+    /// ```
+    pub fn finish(&mut self) -> Option<TextMatch> {
+        // The end of the text is always somewhere a match can end.
+        self.cursor.held.take()
+    }
+}
+
+/// Returns the state for whichever engine `Pattern::engine_for` picked.
+fn new_search<B: BitVector>(
+    engine: Engine,
+    (length, max_distance, allow_transpositions): (usize, usize, bool),
+    start: Boundary,
+) -> Search<B> {
+    by_engine!(engine, length, max_distance, allow_transpositions, start, search => search)
+}
+
+impl Cursor {
+    fn feed<B: BitVector, S: Step<B, Output = usize>>(
+        &mut self,
+        masks: &CharMasks<B>,
+        search: &mut S,
+        chunk: &str,
+        matches: &mut Vec<TextMatch>,
+    ) {
+        for (offset, c) in chunk.char_indices() {
+            if let Some(m) = self.held.take() {
+                if self.bounds.can_end_before(Some(c)) {
+                    matches.push(m);
+                }
+            }
+            let found = search.step(masks.get(c), self.can_start);
+            self.can_start = self.bounds.can_start_after(Some(c));
+            if let Some(distance) = found {
+                let m = TextMatch {
+                    distance,
+                    end: self.position,
+                    byte_end: self.byte_offset + offset + c.len_utf8(),
+                };
+                if self.bounds.end == Boundary::Anywhere {
+                    matches.push(m);
+                } else {
+                    self.held = Some(m);
+                }
+            }
+            self.position += 1;
+        }
+        self.byte_offset += chunk.len();
+    }
+}
@@ -1,12 +1,104 @@
 use super::*;
 use quickcheck::TestResult;
+use std::io;
 
 extern crate bitap_reference as bref;
 
+use bref::baseline::DistanceFn;
+
+// Runs the block once for every word width, with the pattern compiled for it
+// and the name of the width. Takes either a pattern string, or a builder
+// after `builder`.
+macro_rules! for_each_width {
+    (builder $b:expr, |$pattern:ident, $width:ident| $body:block) => {
+        for_each_width!(@run $b, $pattern, $width, $body, u32, u64, u128, usize)
+    };
+    ($p:expr, |$pattern:ident, $width:ident| $body:block) => {
+        for_each_width!(builder PatternBuilder::new($p), |$pattern, $width| $body)
+    };
+    (@run $b:expr, $pattern:ident, $width:ident, $body:block, $($word:ty),*) => {
+        $({
+            let $pattern = $b.build_with_word::<$word>().unwrap();
+            let $width = stringify!($word);
+            $body
+        })*
+    };
+}
+
+// Every engine that can be forced, for checking they all agree.
+const ENGINES: [Engine; 4] = [
+    Engine::Exact,
+    Engine::Static,
+    Engine::Dynamic,
+    Engine::Myers,
+];
+
 fn find_test(ctx: &str, p: &str, t: &str) {
-    let base = bref::find(p, t).unwrap();
-    let actual = Pattern::new(p).unwrap().find(t).collect::<Vec<_>>();
-    assert_eq!(base, actual, "{}: find({:?}, {:?})", ctx, p, t);
+    let len = p.chars().count();
+    let base = ref_find(p, t)
+        .unwrap()
+        .into_iter()
+        .map(|start| MatchSpan {
+            distance: 0,
+            start,
+            end: start + len - 1,
+            byte_start: byte_offset(t, start),
+            byte_end: byte_offset(t, start + len),
+        })
+        .collect::<Vec<_>>();
+    for_each_width!(p, |pattern, width| {
+        let actual = pattern.find(t).collect::<Vec<_>>();
+        assert_eq!(base, actual, "{} {}: find({:?}, {:?})", ctx, width, p, t);
+    });
+}
+
+// Returns the byte offset of the character at index i, or the length of the
+// text if i is past the end.
+fn byte_offset(t: &str, i: usize) -> usize {
+    t.char_indices()
+        .nth(i)
+        .map_or(t.len(), |(offset, _)| offset)
+}
+
+// Checks that the byte offset of every match agrees with its character index,
+// and then drops it so the matches can be compared with the reference.
+fn char_matches<I: Iterator<Item = TextMatch>>(t: &str, matches: I) -> Vec<Match> {
+    matches
+        .map(|m| {
+            assert_eq!(m.byte_end, byte_offset(t, m.end + 1), "{:?} in {:?}", m, t);
+            Match::from(m)
+        })
+        .collect()
+}
+
+fn starts<I: Iterator<Item = MatchSpan>>(spans: I) -> Vec<usize> {
+    spans.map(|m| m.start).collect()
+}
+
+// The reference implementation is limited to single word patterns, so longer
+// patterns are checked against the (much slower) baseline instead.
+fn ref_find(p: &str, t: &str) -> bref::FindResult {
+    if pattern_length_is_valid(p.chars().count()) {
+        bref::find(p, t)
+    } else {
+        bref::baseline::find(p, t)
+    }
+}
+
+fn ref_lev(p: &str, t: &str, k: usize) -> bref::BitapResult {
+    if pattern_length_is_valid(p.chars().count()) {
+        bref::lev(p, t, k)
+    } else {
+        bref::baseline::lev(p, t, k)
+    }
+}
+
+fn ref_osa(p: &str, t: &str, k: usize) -> bref::BitapResult {
+    if pattern_length_is_valid(p.chars().count()) {
+        bref::osa(p, t, k)
+    } else {
+        bref::baseline::osa(p, t, k)
+    }
 }
 
 fn try_static_max_distance(k: usize) -> Option<StaticMaxDistance> {
@@ -18,39 +110,320 @@ fn try_static_max_distance(k: usize) -> Option<StaticMaxDistance> {
 }
 
 fn levenshtein_test(ctx: &str, p: &str, t: &str, k: usize) {
-    let base = ref_result_convert(bref::lev(p, t, k)).unwrap();
-    let actual = Pattern::new(p).unwrap().lev(t, k).collect::<Vec<_>>();
-    assert_eq!(base, actual, "{}: lev({:?}, {:?}, {})", ctx, p, t, k);
-    if let Some(d) = try_static_max_distance(k) {
-        let actual_static = Pattern::new(p)
-            .unwrap()
-            .lev_static(t, d)
-            .collect::<Vec<_>>();
+    let base = ref_result_convert(ref_lev(p, t, k)).unwrap();
+    for_each_width!(p, |pattern, width| {
+        let actual = char_matches(t, pattern.lev(t, k));
         assert_eq!(
-            base, actual_static,
-            "{}: lev_static({:?}, {:?}, {})",
-            ctx, p, t, k
+            base, actual,
+            "{} {}: lev({:?}, {:?}, {})",
+            ctx, width, p, t, k
+        );
+        let actual_myers = char_matches(t, pattern.lev_myers(t, k).unwrap());
+        assert_eq!(
+            base, actual_myers,
+            "{} {}: lev_myers({:?}, {:?}, {})",
+            ctx, width, p, t, k
+        );
+        if let Some(d) = try_static_max_distance(k) {
+            let actual_static = char_matches(t, pattern.lev_static(t, d));
+            assert_eq!(
+                base, actual_static,
+                "{} {}: lev_static({:?}, {:?}, {})",
+                ctx, width, p, t, k
+            );
+        }
+    });
+    for &engine in ENGINES.iter() {
+        let pattern = PatternBuilder::new(p).engine(engine).build().unwrap();
+        let actual = char_matches(t, pattern.lev(t, k));
+        assert_eq!(
+            base, actual,
+            "{} {:?}: lev({:?}, {:?}, {})",
+            ctx, engine, p, t, k
         );
     }
 }
 
 fn optimal_string_alignment_test(ctx: &str, p: &str, t: &str, k: usize) {
-    let base = ref_result_convert(bref::osa(p, t, k)).unwrap();
-    let actual = Pattern::new(p).unwrap().osa(t, k).collect::<Vec<_>>();
-    assert_eq!(base, actual, "{}: osa({:?}, {:?}, {})", ctx, p, t, k);
+    let base = ref_result_convert(ref_osa(p, t, k)).unwrap();
+    for_each_width!(p, |pattern, width| {
+        let actual = char_matches(t, pattern.osa(t, k));
+        assert_eq!(
+            base, actual,
+            "{} {}: osa({:?}, {:?}, {})",
+            ctx, width, p, t, k
+        );
+        let actual_myers = char_matches(t, pattern.osa_myers(t, k).unwrap());
+        assert_eq!(
+            base, actual_myers,
+            "{} {}: osa_myers({:?}, {:?}, {})",
+            ctx, width, p, t, k
+        );
+        if let Some(d) = try_static_max_distance(k) {
+            let actual_static = char_matches(t, pattern.osa_static(t, d));
+            assert_eq!(
+                base, actual_static,
+                "{} {}: osa_static({:?}, {:?}, {})",
+                ctx, width, p, t, k
+            );
+        }
+    });
+    for &engine in ENGINES.iter() {
+        let pattern = PatternBuilder::new(p).engine(engine).build().unwrap();
+        let actual = char_matches(t, pattern.osa(t, k));
+        assert_eq!(
+            base, actual,
+            "{} {:?}: osa({:?}, {:?}, {})",
+            ctx, engine, p, t, k
+        );
+    }
+}
+
+fn damerau_test(ctx: &str, p: &str, t: &str, k: usize) {
+    let base = ref_result_convert(bref::baseline::damerau(p, t, k)).unwrap();
+    let actual = char_matches(t, Pattern::new(p).unwrap().damerau(t, k));
+    assert_eq!(base, actual, "{}: damerau({:?}, {:?}, {})", ctx, p, t, k);
+}
+
+// Every combination of edit counts within the budget that can align the whole
+// pattern with some non-empty text ending at each position, by dynamic
+// programming.
+fn ref_budgeted(
+    p: &str,
+    t: &str,
+    budget: EditCounts,
+    start: Boundary,
+    end: Boundary,
+) -> Vec<BudgetMatch> {
+    let bounds = Bounds {
+        start,
+        end,
+        ..Bounds::default()
+    };
+    let p = p.chars().collect::<Vec<_>>();
+    let t = t.chars().collect::<Vec<_>>();
+    let within = |c: &EditCounts| {
+        c.insertions <= budget.insertions
+            && c.deletions <= budget.deletions
+            && c.substitutions <= budget.substitutions
+            && c.transpositions <= budget.transpositions
+    };
+    let step = |cells: &[EditCounts], f: &dyn Fn(&mut EditCounts)| {
+        cells
+            .iter()
+            .map(|c| {
+                let mut c = *c;
+                f(&mut c);
+                c
+            })
+            .filter(|c| within(c))
+            .collect::<Vec<_>>()
+    };
+    // d[j][i] is every way to align the first i pattern chars with text
+    // ending before position j.
+    let mut d: Vec<Vec<Vec<EditCounts>>> = Vec::new();
+    let mut matches = Vec::new();
+    for j in 0..=t.len() {
+        let mut row: Vec<Vec<EditCounts>> = vec![Vec::new(); p.len() + 1];
+        if bounds.can_start_after(j.checked_sub(1).map(|i| t[i])) {
+            row[0].push(EditCounts::default());
+        }
+        if j > 0 {
+            let inserted = step(&d[j - 1][0], &|c| c.insertions += 1);
+            row[0].extend(inserted);
+        }
+        for i in 1..=p.len() {
+            let mut cell = step(&row[i - 1], &|c| c.deletions += 1);
+            if j > 0 {
+                cell.extend(step(&d[j - 1][i], &|c| c.insertions += 1));
+                if p[i - 1] == t[j - 1] {
+                    cell.extend(step(&d[j - 1][i - 1], &|_| ()));
+                } else {
+                    cell.extend(step(&d[j - 1][i - 1], &|c| c.substitutions += 1));
+                }
+            }
+            if i > 1 && j > 1 && p[i - 1] == t[j - 2] && p[i - 2] == t[j - 1] {
+                cell.extend(step(&d[j - 2][i - 2], &|c| c.transpositions += 1));
+            }
+            cell.sort_by_key(|c| {
+                (
+                    c.total(),
+                    c.insertions,
+                    c.deletions,
+                    c.substitutions,
+                    c.transpositions,
+                )
+            });
+            cell.dedup();
+            row[i] = cell;
+        }
+        // Deleting the whole pattern without inserting anything is empty.
+        let best = row[p.len()]
+            .iter()
+            .find(|c| c.deletions < p.len() || c.insertions > 0);
+        if j > 0 && bounds.can_end_before(t.get(j).copied()) {
+            if let Some(&edits) = best {
+                matches.push(BudgetMatch { edits, end: j - 1 });
+            }
+        }
+        d.push(row);
+    }
+    matches
+}
+
+fn budgeted_test(ctx: &str, p: &str, t: &str, budget: EditCounts) {
+    let base = ref_budgeted(p, t, budget, Boundary::Anywhere, Boundary::Anywhere);
+    let actual = Pattern::new(p)
+        .unwrap()
+        .budgeted(t, budget)
+        .unwrap()
+        .map(|m| {
+            assert_eq!(m.byte_end, byte_offset(t, m.end + 1), "{:?} in {:?}", m, t);
+            BudgetMatch::from(m)
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        base, actual,
+        "{}: budgeted({:?}, {:?}, {:?})",
+        ctx, p, t, budget
+    );
+}
+
+fn hamming_test(ctx: &str, p: &str, t: &str, k: usize) {
+    let base = ref_result_convert(bref::baseline::hamming(p, t, k)).unwrap();
+    let actual = char_matches(t, Pattern::new(p).unwrap().hamming(t, k));
+    assert_eq!(base, actual, "{}: hamming({:?}, {:?}, {})", ctx, p, t, k);
     if let Some(d) = try_static_max_distance(k) {
-        let actual_static = Pattern::new(p)
-            .unwrap()
-            .osa_static(t, d)
-            .collect::<Vec<_>>();
+        let actual_static = char_matches(t, Pattern::new(p).unwrap().hamming_static(t, d));
         assert_eq!(
             base, actual_static,
-            "{}: osa_static({:?}, {:?}, {})",
+            "{}: hamming_static({:?}, {:?}, {})",
             ctx, p, t, k
         );
     }
 }
 
+// Brute forces the start of the longest alignment ending at each match.
+fn ref_spans(p: &str, t: &str, matches: &[TextMatch], distance_fn: DistanceFn) -> Vec<MatchSpan> {
+    let chars = t.chars().collect::<Vec<_>>();
+    matches
+        .iter()
+        .map(|m| {
+            let start = (0..=m.end)
+                .find(|&j| {
+                    let sub_text: String = chars[j..=m.end].iter().collect();
+                    bref::baseline::distance(p, &sub_text, distance_fn) == m.distance
+                })
+                .unwrap();
+            MatchSpan {
+                distance: m.distance,
+                start,
+                end: m.end,
+                byte_start: byte_offset(t, start),
+                byte_end: byte_offset(t, m.end + 1),
+            }
+        })
+        .collect()
+}
+
+fn spans_test(ctx: &str, p: &str, t: &str, k: usize) {
+    let pattern = Pattern::new(p).unwrap();
+    let matches = pattern.lev(t, k).collect::<Vec<_>>();
+    let lev = ref_spans(p, t, &matches, DistanceFn::Levenshtein);
+    let matches = pattern.osa(t, k).collect::<Vec<_>>();
+    let osa = ref_spans(p, t, &matches, DistanceFn::OptimalStringAlignment);
+
+    for_each_width!(p, |pattern, width| {
+        let actual = pattern.lev_spans(t, k).collect::<Vec<_>>();
+        assert_eq!(
+            lev, actual,
+            "{} {}: lev_spans({:?}, {:?}, {})",
+            ctx, width, p, t, k
+        );
+        let actual = pattern.osa_spans(t, k).collect::<Vec<_>>();
+        assert_eq!(
+            osa, actual,
+            "{} {}: osa_spans({:?}, {:?}, {})",
+            ctx, width, p, t, k
+        );
+    });
+}
+
+// Checks that an edit script really does turn the pattern into the text of the
+// match, with the right number of edits.
+fn check_edits(p: &str, t: &str, span: MatchSpan, edits: &[Edit]) -> bool {
+    let p = p.chars().collect::<Vec<_>>();
+    let t = t.chars().collect::<Vec<_>>();
+    let (mut i, mut j, mut distance) = (0, span.start, 0);
+    for edit in edits {
+        let ok = match edit {
+            Edit::Match => i < p.len() && j < t.len() && p[i] == t[j],
+            Edit::Substitute => i < p.len() && j < t.len() && p[i] != t[j],
+            Edit::Transpose => {
+                i + 1 < p.len() && j + 1 < t.len() && p[i] == t[j + 1] && p[i + 1] == t[j]
+            }
+            Edit::Insert => j < t.len(),
+            Edit::Delete => i < p.len(),
+        };
+        if !ok {
+            return false;
+        }
+        match edit {
+            Edit::Match => {}
+            _ => distance += 1,
+        }
+        match edit {
+            Edit::Match | Edit::Substitute => {
+                i += 1;
+                j += 1;
+            }
+            Edit::Transpose => {
+                i += 2;
+                j += 2;
+            }
+            Edit::Insert => j += 1,
+            Edit::Delete => i += 1,
+        }
+    }
+    i == p.len() && j == span.end + 1 && distance == span.distance
+}
+
+fn edits_test(ctx: &str, p: &str, t: &str, k: usize) {
+    for &start in BOUNDARIES.iter() {
+        let pattern = PatternBuilder::new(p).match_start(start).build().unwrap();
+        let ctx = format!("{} {:?}..", ctx, start);
+        bounded_edits_test(&ctx, &pattern, p, t, k);
+    }
+}
+
+fn bounded_edits_test(ctx: &str, pattern: &Pattern<usize>, p: &str, t: &str, k: usize) {
+    for (m, span) in pattern.lev(t, k).zip(pattern.lev_spans(t, k)) {
+        let edits = pattern.lev_edits(t, m).unwrap();
+        assert!(
+            check_edits(p, t, span, &edits),
+            "{}: lev_edits({:?}, {:?}, {:?}) = {:?}",
+            ctx,
+            p,
+            t,
+            m,
+            edits
+        );
+        assert!(!edits.contains(&Edit::Transpose));
+    }
+    for (m, span) in pattern.osa(t, k).zip(pattern.osa_spans(t, k)) {
+        let edits = pattern.osa_edits(t, m).unwrap();
+        assert!(
+            check_edits(p, t, span, &edits),
+            "{}: osa_edits({:?}, {:?}, {:?}) = {:?}",
+            ctx,
+            p,
+            t,
+            m,
+            edits
+        );
+    }
+}
+
 lazy_static! {
     static ref CORPUS: Vec<(&'static str, &'static str)> = {
         vec![
@@ -63,6 +436,27 @@ lazy_static! {
     };
 }
 
+lazy_static! {
+    // Patterns too long to fit in a single word, which should transparently
+    // use the multi-word representation.
+    static ref LONG_CORPUS: Vec<(&'static str, &'static str)> = {
+        vec![
+            (
+                "Acme Industrial Pressure Washer 3000 PSI Model AX-3000-GT Stainless",
+                "sku 1182: Acme Industrial Presure Washer 3000 PSI Model AX-3000-GT Stainless (refurb)",
+            ),
+            (
+                "the quick brown fox jumps over the lazy dog, again and again and again",
+                "said the quick brown fox jumps over teh lazy dog, again and again and agian!",
+            ),
+            (
+                "abababababababababababababababababababababababababababababababababababab",
+                "abababababababababababababababababababababababababababababababababababababa",
+            ),
+        ]
+    };
+}
+
 #[test]
 fn test_find() {
     for (i, (p, t)) in CORPUS.iter().enumerate() {
@@ -93,17 +487,1292 @@ fn test_optimal_string_alignment() {
     }
 }
 
+#[test]
+fn test_damerau() {
+    for (i, (p, t)) in CORPUS.iter().enumerate() {
+        let ctx = format!("case {}", i);
+        let max_k = p.chars().count() + 2; // +2 for good measure
+        for k in 0..=max_k {
+            damerau_test(&ctx, p, t, k);
+        }
+    }
+    // Edits inside of a transposed pair.
+    let cases = [
+        ("abcd", "xx cbd xx", 2),
+        ("abcd", "xx acbd xx", 1),
+        ("abxyc", "xx acb xx", 3),
+        ("ca", "abc", 2),
+        ("abc", "ca", 2),
+        ("abcdef", "zz adcbef zz bxaydc", 3),
+    ];
+    for (i, (p, t, k)) in cases.iter().enumerate() {
+        damerau_test(&format!("damerau case {}", i), p, t, *k);
+    }
+}
+
+#[test]
+fn test_budgeted() {
+    let budgets = [
+        (0, 0, 0, 0),
+        (1, 0, 0, 0),
+        (0, 2, 0, 0),
+        (0, 0, 1, 1),
+        (1, 1, 1, 0),
+        (0, 2, 1, 1),
+        (2, 1, 0, 1),
+    ];
+    for (i, (p, t)) in CORPUS.iter().chain(LONG_CORPUS.iter()).enumerate() {
+        for &(insertions, deletions, substitutions, transpositions) in &budgets {
+            let budget = EditCounts {
+                insertions,
+                deletions,
+                substitutions,
+                transpositions,
+            };
+            budgeted_test(&format!("case {}", i), p, t, budget);
+        }
+    }
+    // With the same budget for everything, the total is the osa distance.
+    let pattern = Pattern::new("hello").unwrap();
+    let budget = EditCounts {
+        insertions: 2,
+        deletions: 2,
+        substitutions: 2,
+        transpositions: 2,
+    };
+    let text = "hlelo wrld, hell oworld";
+    let a = pattern
+        .budgeted(text, budget)
+        .unwrap()
+        .filter(|m| m.distance() <= 2);
+    let b = pattern.osa(text, 2);
+    assert!(a
+        .map(|m| (m.distance(), m.end))
+        .eq(b.map(|m| (m.distance, m.end))));
+
+    // Budgets that would need too many states fail up front, after capping
+    // what the pattern can't use.
+    let huge = EditCounts {
+        insertions: usize::MAX,
+        ..EditCounts::default()
+    };
+    assert_eq!(
+        pattern.budgeted(text, huge).err(),
+        Some(Error::BudgetTooLarge {
+            states: usize::MAX,
+            max: MAX_BUDGET_STATES
+        })
+    );
+    let capped = EditCounts {
+        insertions: 9999,
+        deletions: 100,
+        ..EditCounts::default()
+    };
+    assert!(pattern.budgeted(text, capped).is_ok());
+    let masks = vec![0usize; 5];
+    let budget = EditCounts {
+        insertions: MAX_BUDGET_STATES,
+        ..EditCounts::default()
+    };
+    assert!(budgeted(masks.into_iter(), 5, budget).is_err());
+}
+
+#[test]
+fn test_hamming() {
+    for (i, (p, t)) in CORPUS.iter().enumerate() {
+        let ctx = format!("case {}", i);
+        let max_k = p.chars().count() + 2; // +2 for good measure
+        for k in 0..=max_k {
+            hamming_test(&ctx, p, t, k);
+        }
+    }
+}
+
+#[test]
+fn test_long_patterns() {
+    for (i, (p, t)) in LONG_CORPUS.iter().enumerate() {
+        let ctx = format!("long case {}", i);
+        assert!(!pattern_length_is_valid(p.chars().count()));
+        find_test(&ctx, p, t);
+        for k in 0..=2 {
+            levenshtein_test(&ctx, p, t, k);
+            optimal_string_alignment_test(&ctx, p, t, k);
+            hamming_test(&ctx, p, t, k);
+        }
+        // The damerau baseline is really slow for long patterns.
+        damerau_test(&ctx, p, t, 1);
+    }
+}
+
+// Feeding the text to a searcher in pieces, split at the passed character
+// indexes, should give the same matches as searching it all at once.
+fn searcher_test(ctx: &str, p: &str, t: &str, k: usize, splits: &[usize]) {
+    let pattern = Pattern::new(p).unwrap();
+    let mut chunks = Vec::new();
+    let mut rest = t;
+    let mut last = 0;
+    for &split in splits {
+        let (offset, _) = rest
+            .char_indices()
+            .nth(split - last)
+            .unwrap_or((rest.len(), ' '));
+        chunks.push(&rest[..offset]);
+        rest = &rest[offset..];
+        last = split;
+    }
+    chunks.push(rest);
+
+    let mut lev = pattern.lev_searcher(k);
+    let mut osa = pattern.osa_searcher(k);
+    let lev = chunks.iter().flat_map(|c| lev.feed(c)).collect::<Vec<_>>();
+    let osa = chunks.iter().flat_map(|c| osa.feed(c)).collect::<Vec<_>>();
+    let ctx = format!("{}: {:?}, {:?}, {}, {:?}", ctx, p, chunks, k, splits);
+    assert_eq!(lev, pattern.lev(t, k).collect::<Vec<_>>(), "{}", ctx);
+    assert_eq!(osa, pattern.osa(t, k).collect::<Vec<_>>(), "{}", ctx);
+}
+
+#[test]
+fn test_searcher() {
+    for (i, (p, t)) in CORPUS.iter().chain(LONG_CORPUS.iter()).enumerate() {
+        let ctx = format!("case {}", i);
+        let len = t.chars().count();
+        for k in 0..=3 {
+            searcher_test(&ctx, p, t, k, &[]);
+            searcher_test(&ctx, p, t, k, &[0, len / 2, len / 2, len]);
+            searcher_test(&ctx, p, t, k, &(0..len).step_by(3).collect::<Vec<_>>());
+            searcher_test(&ctx, p, t, k, &(0..len).collect::<Vec<_>>());
+        }
+    }
+    searcher_test("unicode", "wörld", "hellö wörld wörd", 1, &[1, 7, 8]);
+}
+
+// A reader that hands out its data a few bytes at a time, cycling through the
+// passed read sizes, where a size of zero is an interrupted read.
+struct Trickle<'a> {
+    data: &'a [u8],
+    sizes: std::iter::Cycle<std::slice::Iter<'a, usize>>,
+}
+
+impl<'a> io::Read for Trickle<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let size = *self.sizes.next().unwrap();
+        if size == 0 && !self.data.is_empty() {
+            return Err(io::ErrorKind::Interrupted.into());
+        }
+        let n = cmp::min(cmp::min(size, buf.len()), self.data.len());
+        buf[..n].copy_from_slice(&self.data[..n]);
+        self.data = &self.data[n..];
+        Ok(n)
+    }
+}
+
+fn reader_test(ctx: &str, p: &str, t: &str, k: usize, sizes: &[usize]) {
+    let pattern = Pattern::new(p).unwrap();
+    let trickle = || Trickle {
+        data: t.as_bytes(),
+        sizes: sizes.iter().cycle(),
+    };
+    let lev = pattern
+        .lev_reader(trickle(), k)
+        .collect::<io::Result<Vec<_>>>();
+    let osa = pattern
+        .osa_reader(trickle(), k)
+        .collect::<io::Result<Vec<_>>>();
+    let ctx = format!("{}: {:?}, {:?}, {}, {:?}", ctx, p, t, k, sizes);
+    assert_eq!(
+        lev.unwrap(),
+        pattern.lev(t, k).collect::<Vec<_>>(),
+        "{}",
+        ctx
+    );
+    assert_eq!(
+        osa.unwrap(),
+        pattern.osa(t, k).collect::<Vec<_>>(),
+        "{}",
+        ctx
+    );
+}
+
+#[test]
+fn test_reader() {
+    for (i, (p, t)) in CORPUS.iter().chain(LONG_CORPUS.iter()).enumerate() {
+        let ctx = format!("case {}", i);
+        for k in 0..=2 {
+            reader_test(&ctx, p, t, k, &[8192]);
+            reader_test(&ctx, p, t, k, &[1]);
+            reader_test(&ctx, p, t, k, &[3, 0, 1, 2]);
+        }
+    }
+    let text = "hellö wörld 😀 wörd ".repeat(1000);
+    reader_test("unicode", "wörld", &text, 1, &[1, 2, 3]);
+    reader_test("unicode long", "wörld", &text, 1, &[10_000]);
+
+    // Matches before invalid text are still returned.
+    let pattern = Pattern::new("abc").unwrap();
+    for bad in [&b"abc\xffabc"[..], &b"abc\xe2\x82"[..]] {
+        let mut matches = pattern.lev_reader(bad, 0);
+        let m = matches.next().unwrap().unwrap();
+        assert_eq!(m.byte_end, 3);
+        let e = matches.next().unwrap().unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+        assert!(matches.next().is_none());
+    }
+}
+
+// The fixed distance adapters should find the same matches as the regular
+// ones for any K, including ones bigger than the pattern.
+fn fixed_distance_test<const K: usize, W: BitVector>(p: &str, t: &str) {
+    let pattern = PatternBuilder::new(p).build_with_word::<W>().unwrap();
+    let masks = match &pattern.masks {
+        Masks::Word(masks) => masks,
+        _ => panic!("{:?} doesn't fit in a word", p),
+    };
+    let (len, ctx) = (pattern.len(), format!("{:?}, {:?}, {}", p, t, K));
+    let base = levenshtein(mask_iter(masks, t.chars()), len, K).unwrap();
+    let fixed = levenshtein_k::<K, _>(mask_iter(masks, t.chars()), len).unwrap();
+    assert_eq!(
+        base.collect::<Vec<_>>(),
+        fixed.collect::<Vec<_>>(),
+        "lev {}",
+        ctx
+    );
+    let base = optimal_string_alignment(mask_iter(masks, t.chars()), len, K).unwrap();
+    let fixed = optimal_string_alignment_k::<K, _>(mask_iter(masks, t.chars()), len).unwrap();
+    assert_eq!(
+        base.collect::<Vec<_>>(),
+        fixed.collect::<Vec<_>>(),
+        "osa {}",
+        ctx
+    );
+}
+
+#[test]
+fn test_fixed_distance() {
+    for (p, t) in CORPUS.iter() {
+        fixed_distance_test::<0, usize>(p, t);
+        fixed_distance_test::<2, usize>(p, t);
+        fixed_distance_test::<4, usize>(p, t);
+        fixed_distance_test::<5, u32>(p, t);
+        fixed_distance_test::<100, u128>(p, t);
+    }
+    for (p, t) in LONG_CORPUS.iter() {
+        fixed_distance_test::<3, Wide<4>>(p, t);
+        fixed_distance_test::<7, Wide<4>>(p, t);
+    }
+}
+
+#[test]
+fn test_spans() {
+    for (i, (p, t)) in CORPUS.iter().chain(LONG_CORPUS.iter()).enumerate() {
+        let ctx = format!("case {}", i);
+        for k in 0..=3 {
+            spans_test(&ctx, p, t, k);
+        }
+    }
+}
+
+#[test]
+fn test_edits() {
+    for (i, (p, t)) in CORPUS.iter().chain(LONG_CORPUS.iter()).enumerate() {
+        let ctx = format!("case {}", i);
+        for k in 0..=3 {
+            edits_test(&ctx, p, t, k);
+        }
+    }
+    let pattern = Pattern::new("alex").unwrap();
+    let m = TextMatch {
+        distance: 0,
+        end: 3,
+        byte_end: 4,
+    };
+    assert_eq!(pattern.lev_edits("alex", m), Some(vec![Edit::Match; 4]));
+    assert_eq!(pattern.lev_edits("alxe", m), None);
+    assert_eq!(pattern.lev_edits("ale", m), None);
+    assert_eq!(pattern.lev_edits("😀lex", m), None);
+
+    // The edits start where the span does, even when a longer alignment
+    // would start somewhere a match can't.
+    let pattern = PatternBuilder::new("ac")
+        .match_start(Boundary::Word)
+        .build()
+        .unwrap();
+    let m = pattern.lev("c cc ", 2).nth(2).unwrap();
+    assert_eq!(starts(pattern.lev_spans("c cc ", 2)).get(2), Some(&2));
+    assert_eq!(cigar(&pattern.lev_edits("c cc ", m).unwrap()), "1D1=");
+}
+
+// A simple, non-streaming version of local_minima: split the matches into
+// plateaus of neighbouring matches with the same distance, and keep the first
+// match of every plateau that's lower than both of its neighbours.
+fn ref_local_minima(matches: &[Match]) -> Vec<Match> {
+    let adjacent = |a: &Match, b: &Match| a.end + 1 == b.end;
+    let mut plateaus: Vec<&[Match]> = Vec::new();
+    let mut start = 0;
+    for i in 1..=matches.len() {
+        if i == matches.len()
+            || !adjacent(&matches[i - 1], &matches[i])
+            || matches[i - 1].distance != matches[i].distance
+        {
+            plateaus.push(&matches[start..i]);
+            start = i;
+        }
+    }
+    let higher = |a: Option<&Match>, b: &Match| match a {
+        Some(a) => !(adjacent(a, b) || adjacent(b, a)) || a.distance > b.distance,
+        None => true,
+    };
+    plateaus
+        .iter()
+        .enumerate()
+        .filter(|(i, plateau)| {
+            let first = &plateau[0];
+            let last = &plateau[plateau.len() - 1];
+            let before = if *i > 0 { plateaus[i - 1].last() } else { None };
+            let after = plateaus.get(i + 1).map(|p| &p[0]);
+            higher(before, first) && higher(after, last)
+        })
+        .map(|(_, plateau)| plateau[0])
+        .collect()
+}
+
+#[test]
+fn test_local_minima() {
+    let m = |distance, end| Match { distance, end };
+    let cases = vec![
+        (vec![], vec![]),
+        (
+            vec![m(2, 0), m(1, 1), m(0, 2), m(1, 3), m(2, 4)],
+            vec![m(0, 2)],
+        ),
+        (
+            vec![m(1, 0), m(0, 1), m(1, 2), m(0, 3), m(1, 4)],
+            vec![m(0, 1), m(0, 3)],
+        ),
+        (vec![m(1, 0), m(1, 1), m(1, 2)], vec![m(1, 0)]),
+        (
+            vec![m(0, 0), m(1, 1), m(1, 2), m(0, 3)],
+            vec![m(0, 0), m(0, 3)],
+        ),
+        (vec![m(2, 0), m(1, 1), m(1, 2), m(0, 3)], vec![m(0, 3)]),
+        (vec![m(1, 0), m(1, 2), m(0, 3)], vec![m(1, 0), m(0, 3)]),
+    ];
+    for (input, expected) in cases {
+        assert_eq!(ref_local_minima(&input), expected, "ref: {:?}", input);
+        let actual = local_minima(input.iter().cloned()).collect::<Vec<_>>();
+        assert_eq!(actual, expected, "{:?}", input);
+    }
+    for (p, t) in CORPUS.iter() {
+        let pattern = Pattern::new(p).unwrap();
+        for k in 0..=3 {
+            let lev = char_matches(t, pattern.lev(t, k));
+            let best = char_matches(t, pattern.lev_best(t, k));
+            assert_eq!(best, ref_local_minima(&lev));
+            let osa = char_matches(t, pattern.osa(t, k));
+            let best = char_matches(t, pattern.osa_best(t, k));
+            assert_eq!(best, ref_local_minima(&osa));
+        }
+    }
+}
+
+// Decodes bytes as Latin-1, so that char indexes are the same as byte offsets.
+fn latin1(bytes: &[u8]) -> String {
+    bytes.iter().map(|&b| b as char).collect()
+}
+
+fn bytes_test(ctx: &str, p: &[u8], t: &[u8], k: usize) {
+    let pattern = BytePattern::new(p).unwrap();
+    let base = Pattern::new(&latin1(p)).unwrap();
+    let text = latin1(t);
+    let lev = char_matches(&text, base.lev(&text, k));
+    let osa = char_matches(&text, base.osa(&text, k));
+    let ctx = format!("{}: {:?}, {:?}, {}", ctx, p, t, k);
+    assert_eq!(
+        starts(base.find(&text)),
+        pattern.find(t).collect::<Vec<_>>(),
+        "{}",
+        ctx
+    );
+    assert_eq!(lev, pattern.lev(t, k).collect::<Vec<_>>(), "{}", ctx);
+    assert_eq!(osa, pattern.osa(t, k).collect::<Vec<_>>(), "{}", ctx);
+    if let Some(d) = try_static_max_distance(k) {
+        let actual = pattern.lev_static(t, d).collect::<Vec<_>>();
+        assert_eq!(lev, actual, "{}", ctx);
+    }
+    if let Some(d) = try_static_max_distance(k) {
+        let actual = pattern.osa_static(t, d).collect::<Vec<_>>();
+        assert_eq!(osa, actual, "{}", ctx);
+    }
+    let hamming = char_matches(&text, base.hamming(&text, k));
+    assert_eq!(
+        hamming,
+        pattern.hamming(t, k).collect::<Vec<_>>(),
+        "{}",
+        ctx
+    );
+    if let Some(d) = try_static_max_distance(k) {
+        let actual = pattern.hamming_static(t, d).collect::<Vec<_>>();
+        assert_eq!(hamming, actual, "{}", ctx);
+    }
+}
+
+#[test]
+fn test_bytes() {
+    let cases: Vec<(&[u8], &[u8])> = vec![
+        (b"alex", b"hey im alex, how are you?"),
+        (b"\xff\x00\xfe", b"\x00\xff\x00\xfe\xff\xfe\x00\xff\x00"),
+        (b"caf\xe9", b"latin-1 caf\xe9 and utf-8 caf\xc3\xa9"),
+        (
+            b"abababababababababababababababababababababababababababababababababababab",
+            b"abababababababababababababababababababababababababababababababababababababa",
+        ),
+    ];
+    for (i, (p, t)) in cases.iter().enumerate() {
+        let ctx = format!("case {}", i);
+        for k in 0..=3 {
+            bytes_test(&ctx, p, t, k);
+        }
+    }
+    assert!(BytePattern::new(b"").is_err());
+    assert!(BytePattern::new(&[0; MAX_PATTERN_LENGTH + 1]).is_err());
+}
+
+// Too slow to check against the baseline, so just check an obvious case.
+#[test]
+fn test_very_long_pattern() {
+    let p = "0123456789".repeat(15);
+    let mut typo = p.clone();
+    typo.replace_range(70..71, "x");
+    let pattern = Pattern::new(&p).unwrap();
+    assert_eq!(starts(pattern.find(&format!("zz{}", p))), vec![2]);
+    assert_eq!(
+        pattern.lev(&format!("xx{}yy", typo), 1).collect::<Vec<_>>(),
+        vec![TextMatch {
+            distance: 1,
+            end: 151,
+            byte_end: 152
+        }]
+    );
+}
+
+#[test]
+fn test_char_masks() {
+    // Characters from the Latin-1 table, the fallback list, and both.
+    let masks = CharMasks::<usize>::new(&literal("aé😀бaб\u{ff}\u{100}"), false, &[]);
+    assert_eq!(masks.get('a'), !0b0001_0001);
+    assert_eq!(masks.get('é'), !0b0000_0010);
+    assert_eq!(masks.get('😀'), !0b0000_0100);
+    assert_eq!(masks.get('б'), !0b0010_1000);
+    assert_eq!(masks.get('\u{ff}'), !0b0100_0000);
+    assert_eq!(masks.get('\u{100}'), !0b1000_0000);
+    assert_eq!(masks.get('z'), !0);
+    assert_eq!(masks.get('я'), !0);
+}
+
+#[test]
+fn test_case_insensitive() {
+    let pattern = PatternBuilder::new("WoRlD")
+        .case_insensitive(true)
+        .build()
+        .unwrap();
+    assert_eq!(
+        starts(pattern.find("hello world, HELLO WORLD")),
+        vec![6, 19]
+    );
+    let pattern = PatternBuilder::new("straße")
+        .case_insensitive(true)
+        .build()
+        .unwrap();
+    assert_eq!(starts(pattern.find("STRAẞE strasse")), vec![0]);
+    let pattern = PatternBuilder::new("σοφός")
+        .case_insensitive(true)
+        .build()
+        .unwrap();
+    assert_eq!(starts(pattern.find("ΣΟΦΌΣ σοφόσ")), vec![0, 6]);
+    let pattern = PatternBuilder::new("kelvin")
+        .case_insensitive(true)
+        .build()
+        .unwrap();
+    assert_eq!(starts(pattern.find("\u{212A}ELVIN")), vec![0]);
+    // Dotless i is its own thing, and shouldn't match a regular "i".
+    let pattern = PatternBuilder::new("ı")
+        .case_insensitive(true)
+        .build()
+        .unwrap();
+    assert_eq!(starts(pattern.find("iIı")), vec![2]);
+}
+
+#[test]
+fn test_case_variants() {
+    for c in (0..=0x10FFFF).filter_map(std::char::from_u32) {
+        if case::cased_between(c, c).next().is_none() {
+            assert_eq!(case::case_variants(c), vec![c], "{:?}", c);
+        }
+        let folded = case::fold(c);
+        if folded == c {
+            continue;
+        }
+        assert!(case::case_variants(c).contains(&folded), "{:?}", c);
+        assert!(case::case_variants(folded).contains(&c), "{:?}", c);
+    }
+}
+
+fn literal(p: &str) -> Vec<CharClass> {
+    p.chars().map(CharClass::Char).collect()
+}
+
+fn accepts(class: &CharClass, c: char) -> bool {
+    let within = |ranges: &[std::ops::RangeInclusive<char>]| ranges.iter().any(|r| r.contains(&c));
+    match class {
+        CharClass::Char(p) => *p == c,
+        CharClass::Set(ranges) => within(ranges),
+        CharClass::NotSet(ranges) => !within(ranges),
+        CharClass::Any => true,
+    }
+}
+
+// Sellers' algorithm: the plain dynamic programming version of what bitap
+// computes, where the pattern can start matching anywhere in the text.
+fn ref_classes(classes: &[CharClass], t: &str, k: usize, transpositions: bool) -> Vec<Match> {
+    let t = t.chars().collect::<Vec<_>>();
+    let m = classes.len();
+    let mut prev2 = vec![0; m + 1];
+    let mut prev = (0..=m).collect::<Vec<_>>();
+    let mut matches = Vec::new();
+    for j in 1..=t.len() {
+        let mut row = vec![0; m + 1];
+        for i in 1..=m {
+            let cost = if accepts(&classes[i - 1], t[j - 1]) {
+                0
+            } else {
+                1
+            };
+            row[i] = cmp::min(prev[i - 1] + cost, cmp::min(prev[i], row[i - 1]) + 1);
+            if transpositions
+                && i > 1
+                && j > 1
+                && accepts(&classes[i - 1], t[j - 2])
+                && accepts(&classes[i - 2], t[j - 1])
+            {
+                row[i] = cmp::min(row[i], prev2[i - 2] + 1);
+            }
+        }
+        if row[m] <= k {
+            matches.push(Match {
+                distance: row[m],
+                end: j - 1,
+            });
+        }
+        prev2 = std::mem::replace(&mut prev, row);
+    }
+    matches
+}
+
+fn classes_test(ctx: &str, classes: &[CharClass], t: &str, k: usize) {
+    let pattern = PatternBuilder::from_classes(classes).build().unwrap();
+    let exact = ref_classes(classes, t, 0, false)
+        .into_iter()
+        .map(|m| m.end + 1 - classes.len())
+        .collect::<Vec<_>>();
+    assert_eq!(starts(pattern.find(t)), exact, "{}", ctx);
+    let lev = ref_classes(classes, t, k, false);
+    assert_eq!(char_matches(t, pattern.lev(t, k)), lev, "{}", ctx);
+    let osa = ref_classes(classes, t, k, true);
+    assert_eq!(char_matches(t, pattern.osa(t, k)), osa, "{}", ctx);
+}
+
+#[test]
+fn test_syntax() {
+    use CharClass::*;
+    let parse = |p: &str| class::parse(p);
+    assert_eq!(
+        parse("co?r"),
+        Ok(vec![Char('c'), Char('o'), Any, Char('r')])
+    );
+    assert_eq!(
+        parse("[0-9]{3}-"),
+        Ok(vec![
+            Set(vec!['0'..='9']),
+            Set(vec!['0'..='9']),
+            Set(vec!['0'..='9']),
+            Char('-'),
+        ])
+    );
+    assert_eq!(
+        parse("[^a-cx][!]z-]"),
+        Ok(vec![
+            NotSet(vec!['a'..='c', 'x'..='x']),
+            NotSet(vec![']'..=']', 'z'..='z', '-'..='-']),
+        ])
+    );
+    assert_eq!(
+        parse(r"\?\[[\]\-]a{0}"),
+        Ok(vec![Char('?'), Char('['), Set(vec![']'..=']', '-'..='-'])])
+    );
+    assert_eq!(parse("[-a]"), Ok(vec![Set(vec!['-'..='-', 'a'..='a'])]));
+    for bad in &["[abc", "[]", "[z-a]", "{2}", "a{", "a{2", "a{x}", "a\\"] {
+        assert!(parse(bad).is_err(), "{:?}", bad);
+    }
+    assert_eq!(parse("ab{x}"), Err(Error::InvalidSyntax { position: 2 }));
+    assert_eq!(parse("é[^a\\"), Err(Error::InvalidSyntax { position: 1 }));
+    assert!(PatternBuilder::new("a{0}").syntax(true).build().is_err());
+    assert!(PatternBuilder::new("?{255}").syntax(true).build().is_ok());
+    assert!(PatternBuilder::new("?{256}").syntax(true).build().is_err());
+    // Without the option, it's all literal.
+    let pattern = PatternBuilder::new("a?[b]").build().unwrap();
+    assert_eq!(starts(pattern.find("ab a?[b]")), vec![3]);
+}
+
+#[test]
+fn test_classes() {
+    let texts = [
+        "color colour colr coler c0l0r",
+        "item 042-item 04-item 0420-item 42x-item",
+        "фото фата пото ф0то 😀😀 \u{10FFFF}",
+    ];
+    let patterns = [
+        "colo?r",
+        "[0-9]{3}-item",
+        "c[^aeiu]l?r",
+        "ф[!а]т[оа]",
+        "[\u{100}-\u{10FFFF}][^\u{0}-я]",
+        "??-",
+    ];
+    for (i, p) in patterns.iter().enumerate() {
+        let classes = class::parse(p).unwrap();
+        for (j, t) in texts.iter().enumerate() {
+            for k in 0..3 {
+                classes_test(&format!("test_classes {} {} {}", i, j, k), &classes, t, k);
+            }
+        }
+    }
+    // A very long pattern with wildcards still works.
+    let classes = class::parse("?{70}[0-9]{60}").unwrap();
+    let text = format!("{}{}", "x".repeat(80), "1".repeat(70));
+    classes_test("test_classes long", &classes, &text, 2);
+}
+
+#[test]
+fn test_classes_case_insensitive() {
+    let pattern = PatternBuilder::new("[a-c]x[^k]")
+        .syntax(true)
+        .case_insensitive(true)
+        .build()
+        .unwrap();
+    assert_eq!(
+        starts(pattern.find("AXK bXk CX\u{212A} cxz BXZ")),
+        vec![12, 16]
+    );
+}
+
+#[test]
+fn test_huge_classes_case_insensitive() {
+    // Only ASCII is left, less the letters that the Kelvin sign and long s
+    // are variants of.
+    let pattern = PatternBuilder::new("[^\u{80}-\u{10FFFF}]{10}")
+        .syntax(true)
+        .case_insensitive(true)
+        .build()
+        .unwrap();
+    assert_eq!(
+        starts(pattern.find("abcdefghijé0123456789éabcdefghikéssssssssss")),
+        vec![0, 11]
+    );
+}
+
+#[test]
+fn test_text_wildcards() {
+    let pattern = PatternBuilder::new("hello")
+        .text_wildcards(vec!['\u{FFFD}', '_', '😀'])
+        .build()
+        .unwrap();
+    assert_eq!(
+        starts(pattern.find("h\u{FFFD}llo _____ he😀lp")),
+        vec![0, 6]
+    );
+    assert_eq!(
+        pattern.lev("he😀lp", 1).collect::<Vec<_>>(),
+        vec![
+            TextMatch {
+                distance: 1,
+                end: 3,
+                byte_end: 7
+            },
+            TextMatch {
+                distance: 1,
+                end: 4,
+                byte_end: 8
+            }
+        ]
+    );
+    // Wildcards are exempt from case folding.
+    let pattern = PatternBuilder::new("ab")
+        .case_insensitive(true)
+        .text_wildcards(vec!['x'])
+        .build()
+        .unwrap();
+    assert_eq!(starts(pattern.find("aX ax")), vec![3]);
+}
+
+// Searching with a set should give the same matches as searching for every
+// pattern on its own.
+fn pattern_set_test(ctx: &str, patterns: &[&str], t: &str, k: usize) {
+    let set = PatternSet::new(patterns).unwrap();
+    let compiled = patterns
+        .iter()
+        .map(|p| Pattern::new(p).unwrap())
+        .collect::<Vec<_>>();
+
+    let mut find = Vec::new();
+    let mut lev = Vec::new();
+    let mut osa = Vec::new();
+    for (i, p) in compiled.iter().enumerate() {
+        find.extend(p.find(t).map(|m| (m.end, i, m)));
+        lev.extend(p.lev(t, k).map(|m| (m.end, i, m)));
+        osa.extend(p.osa(t, k).map(|m| (m.end, i, m)));
+    }
+    find.sort_by_key(|&(end, i, _)| (end, i));
+    lev.sort_by_key(|&(end, i, _)| (end, i));
+    osa.sort_by_key(|&(end, i, _)| (end, i));
+
+    let find = find.into_iter().map(|(_, i, m)| (i, m));
+    assert_eq!(
+        set.find(t).collect::<Vec<_>>(),
+        find.collect::<Vec<_>>(),
+        "{}",
+        ctx
+    );
+    let lev = lev.into_iter().map(|(_, i, m)| (i, m));
+    assert_eq!(
+        set.lev(t, k).collect::<Vec<_>>(),
+        lev.collect::<Vec<_>>(),
+        "{}",
+        ctx
+    );
+    let osa = osa.into_iter().map(|(_, i, m)| (i, m));
+    assert_eq!(
+        set.osa(t, k).collect::<Vec<_>>(),
+        osa.collect::<Vec<_>>(),
+        "{}",
+        ctx
+    );
+}
+
+#[test]
+fn test_pattern_set() {
+    let mut patterns = vec!["a", "abc", "bca", "hello", "wrold", "ab"];
+    for p in LONG_CORPUS.iter().take(2) {
+        patterns.push(p.0);
+    }
+    // Enough short patterns to need several words.
+    let codes = (0..40).map(|i| format!("sku{:02}", i)).collect::<Vec<_>>();
+    patterns.extend(codes.iter().map(|s| s.as_str()));
+    let text = format!(
+        "{} abcab hello world sku07 skuu12 sk31 {} sku39",
+        LONG_CORPUS[0].1, LONG_CORPUS[1].1
+    );
+    for k in 0..4 {
+        pattern_set_test(&format!("test_pattern_set {}", k), &patterns, &text, k);
+    }
+    assert!(PatternSet::new(["abc", ""]).is_err());
+    assert!(PatternSet::new(vec!["a".repeat(MAX_PATTERN_LENGTH + 1)]).is_err());
+    let empty = PatternSet::new(Vec::<String>::new()).unwrap();
+    assert!(empty.is_empty());
+    assert_eq!(empty.lev("abc", 1).next(), None);
+}
+
+#[test]
+fn test_pattern_length_limits() {
+    assert!(Pattern::new("").is_err());
+    assert!(Pattern::new(&"a".repeat(MAX_PATTERN_LENGTH)).is_ok());
+    assert!(Pattern::new(&"a".repeat(MAX_PATTERN_LENGTH + 1)).is_err());
+}
+
+#[test]
+fn test_word_widths() {
+    fn masks<W: BitVector>(len: usize) -> Masks<W> {
+        let p = "a".repeat(len);
+        PatternBuilder::new(&p).build_with_word().unwrap().masks
+    }
+    assert!(matches!(masks::<u32>(31), Masks::Word(_)));
+    assert!(matches!(masks::<u32>(32), Masks::Wide2(_)));
+    assert!(matches!(masks::<u128>(127), Masks::Word(_)));
+    assert!(matches!(masks::<u128>(128), Masks::Wide4(_)));
+    let p = "a".repeat(256);
+    assert!(PatternBuilder::new(&p).build_with_word::<u128>().is_err());
+    let masks = vec![0u128; 127];
+    assert_eq!(find(masks.into_iter(), 127).unwrap().next(), Some(0));
+    let masks = vec![0u32; 32];
+    assert_eq!(
+        find(masks.into_iter(), 32).err(),
+        Some(Error::PatternTooLong { len: 32, max: 31 })
+    );
+}
+
+fn distance_test(a: &str, b: &str) {
+    let lev = bref::baseline::distance(a, b, DistanceFn::Levenshtein);
+    let osa = bref::baseline::distance(a, b, DistanceFn::OptimalStringAlignment);
+    assert_eq!(levenshtein_distance(a, b), lev, "lev({:?}, {:?})", a, b);
+    assert_eq!(osa_distance(a, b), osa, "osa({:?}, {:?})", a, b);
+    for k in 0..lev + 2 {
+        assert_eq!(
+            within_distance(a, b, k),
+            lev <= k,
+            "within_distance({:?}, {:?}, {})",
+            a,
+            b,
+            k
+        );
+        let within = |d: usize| Some(d).filter(|&d| d <= k);
+        assert_eq!(
+            distance::table(a, b, k, false),
+            within(lev),
+            "table lev {}",
+            k
+        );
+        assert_eq!(
+            distance::table(a, b, k, true),
+            within(osa),
+            "table osa {}",
+            k
+        );
+    }
+}
+
+#[test]
+fn test_distance() {
+    distance_test("", "");
+    distance_test("", "abc");
+    distance_test("kitten", "sitting");
+    distance_test("sitting", "kitten");
+    distance_test("abcd", "acbd");
+    distance_test("ca", "abc");
+    distance_test("ab", "ba");
+    distance_test("abc", "xyz");
+    distance_test("aaa", "aaaaaa");
+    distance_test("hello", "olleh");
+    distance_test("wörld", "wrold");
+    let long = "abcdefghij".repeat(10);
+    distance_test(&long, &long.replace("e", "x"));
+    distance_test(&long, &long[3..]);
+    distance_test(&"xy".repeat(100), &"yx".repeat(100));
+    // The whole distance takes a single pass, however big it is.
+    let far = "b".repeat(32000);
+    assert_eq!(levenshtein_distance("a", &far), 32000);
+    assert_eq!(osa_distance(&far, "ab"), 31999);
+    // Strings too long to fit in a bit vector take the slow way.
+    let long = "the quick brown fox jumps over the lazy dog ".repeat(8);
+    assert!(long.chars().count() > MAX_PATTERN_LENGTH);
+    distance_test(&long, &long);
+    distance_test(&long, &long.replace("o", "0"));
+    distance_test(&long, &long.replace("ju", "uj").replace("dog", "god"));
+    distance_test(&long, &format!("{}{}", &long[5..], "fox"));
+    distance_test(&long, &"xy".repeat(200));
+    // Strings that are too far apart in length don't need to be searched.
+    let too_long = "a".repeat(MAX_PATTERN_LENGTH + 1);
+    assert!(!within_distance(&too_long, "a", 1));
+}
+
+// Searches for every long enough prefix of the pattern separately, and keeps
+// the best one at each position. Matches can start and end anywhere unless
+// `bounds` says otherwise.
+fn ref_prefix(
+    p: &str,
+    t: &str,
+    k: usize,
+    min_length: usize,
+    bounds: Option<(Boundary, Boundary)>,
+) -> Vec<(usize, Match)> {
+    let chars = p.chars().collect::<Vec<_>>();
+    let mut best: Vec<Option<(usize, Match)>> = vec![None; t.chars().count()];
+    for len in cmp::max(min_length, 1)..=chars.len() {
+        let prefix = chars[..len].iter().collect::<String>();
+        let matches = match bounds {
+            Some((start, end)) => ref_bounded(&prefix, t, k, DistanceFn::Levenshtein, start, end),
+            None => ref_result_convert(ref_lev(&prefix, t, k)).unwrap(),
+        };
+        for m in matches {
+            let better = match best[m.end] {
+                Some((_, b)) => m.distance <= b.distance,
+                None => true,
+            };
+            if better {
+                best[m.end] = Some((len, m));
+            }
+        }
+    }
+    best.into_iter().flatten().collect()
+}
+
+fn prefix_test(ctx: &str, p: &str, t: &str, k: usize, min_length: usize) {
+    let base = ref_prefix(p, t, k, min_length, None);
+    for_each_width!(p, |pattern, width| {
+        let actual = pattern
+            .lev_prefix(t, k, min_length)
+            .map(|(consumed, m)| (consumed, char_matches(t, iter::once(m))[0]))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            base, actual,
+            "{} {}: lev_prefix({:?}, {:?}, {}, {})",
+            ctx, width, p, t, k, min_length
+        );
+    });
+}
+
+#[test]
+fn test_prefix() {
+    prefix_test("typed so far", "autocomplete", "auto", 0, 2);
+    prefix_test("typo", "autocomplete", "avto", 1, 3);
+    prefix_test("whole pattern", "abc", "xxabcxx", 1, 2);
+    prefix_test("too short", "abcdef", "ab abc", 0, 3);
+    prefix_test("min length zero", "abc", "xaxb", 0, 0);
+    prefix_test("min length past end", "abc", "abc", 0, 4);
+    prefix_test("distance past end", "abc", "xyz", 5, 1);
+    prefix_test("words", "kangaroo", "the kanga and kangeroo", 2, 4);
+    prefix_test("unicode", "жёлтый", "жел", 1, 3);
+    prefix_test("long", &"abcdefghij".repeat(10), "abcdxfghijabcd", 1, 5);
+}
+
+const BOUNDARIES: [Boundary; 3] = [Boundary::Anywhere, Boundary::Word, Boundary::Text];
+
+// Checks every non-empty substring that starts and ends in an allowed place,
+// keeping the lowest distance at each end.
+fn ref_bounded(
+    p: &str,
+    t: &str,
+    k: usize,
+    distance_fn: DistanceFn,
+    start: Boundary,
+    end: Boundary,
+) -> Vec<Match> {
+    let bounds = Bounds {
+        start,
+        end,
+        ..Bounds::default()
+    };
+    let chars = t.chars().collect::<Vec<_>>();
+    let mut matches = Vec::new();
+    for e in 0..chars.len() {
+        if !bounds.can_end_before(chars.get(e + 1).copied()) {
+            continue;
+        }
+        let best = (0..=e)
+            .filter(|&s| bounds.can_start_after(s.checked_sub(1).map(|i| chars[i])))
+            .map(|s| {
+                let window = chars[s..=e].iter().collect::<String>();
+                bref::baseline::distance(p, &window, distance_fn)
+            })
+            .min();
+        if let Some(distance) = best.filter(|&d| d <= k) {
+            matches.push(Match { distance, end: e });
+        }
+    }
+    matches
+}
+
+fn bounded_test(ctx: &str, p: &str, t: &str, k: usize) {
+    use DistanceFn::*;
+    // Hamming distance is infinite for anything that isn't as long as the
+    // pattern, so keep that out.
+    let hamming_k = cmp::min(k, p.chars().count());
+    for &start in BOUNDARIES.iter() {
+        for &end in BOUNDARIES.iter() {
+            let lev = ref_bounded(p, t, k, Levenshtein, start, end);
+            let osa = ref_bounded(p, t, k, OptimalStringAlignment, start, end);
+            let damerau = ref_bounded(p, t, k, DamerauLevenshtein, start, end);
+            let hamming = ref_bounded(p, t, hamming_k, Hamming, start, end);
+            let exact = ref_bounded(p, t, 0, Levenshtein, start, end);
+            let builder = PatternBuilder::new(p).match_start(start).match_end(end);
+            for_each_width!(builder builder, |pattern, width| {
+                let ctx = format!("{} {} {:?}..{:?}", ctx, width, start, end);
+                let actual = pattern
+                    .find(t)
+                    .map(|m| Match {
+                        distance: m.distance,
+                        end: m.end,
+                    })
+                    .collect::<Vec<_>>();
+                assert_eq!(exact, actual, "{}: find({:?}, {:?})", ctx, p, t);
+                let actual = char_matches(t, pattern.lev(t, k));
+                assert_eq!(lev, actual, "{}: lev({:?}, {:?}, {})", ctx, p, t, k);
+                let actual = char_matches(t, pattern.osa(t, k));
+                assert_eq!(osa, actual, "{}: osa({:?}, {:?}, {})", ctx, p, t, k);
+                let actual = char_matches(t, pattern.damerau(t, k));
+                assert_eq!(damerau, actual, "{}: damerau({:?}, {:?})", ctx, p, t);
+                let actual = char_matches(t, pattern.hamming(t, k));
+                assert_eq!(hamming, actual, "{}: hamming({:?}, {:?})", ctx, p, t);
+                if try_static_max_distance(k).is_some() {
+                    let d = || try_static_max_distance(k).unwrap();
+                    let actual = char_matches(t, pattern.lev_static(t, d()));
+                    assert_eq!(lev, actual, "{}: lev_static({:?}, {:?}, {})", ctx, p, t, k);
+                    let actual = char_matches(t, pattern.osa_static(t, d()));
+                    assert_eq!(osa, actual, "{}: osa_static({:?}, {:?}, {})", ctx, p, t, k);
+                    let actual = char_matches(t, pattern.hamming_static(t, d()));
+                    assert_eq!(hamming, actual, "{}: hamming_static({:?}, {:?})", ctx, p, t);
+                }
+                if start == Boundary::Word {
+                    let err = Some(Error::UnsupportedBounds);
+                    assert_eq!(pattern.lev_myers(t, k).err(), err, "{}", ctx);
+                    assert_eq!(pattern.osa_myers(t, k).err(), err, "{}", ctx);
+                } else {
+                    let actual = char_matches(t, pattern.lev_myers(t, k).unwrap());
+                    assert_eq!(lev, actual, "{}: lev_myers({:?}, {:?}, {})", ctx, p, t, k);
+                    let actual = char_matches(t, pattern.osa_myers(t, k).unwrap());
+                    assert_eq!(osa, actual, "{}: osa_myers({:?}, {:?}, {})", ctx, p, t, k);
+                }
+
+                // Searchers can't see past the end of a chunk, so feed them a
+                // character at a time.
+                let mut searcher = pattern.osa_searcher(k);
+                let mut fed = Vec::new();
+                for c in t.chars() {
+                    fed.extend(searcher.feed(&c.to_string()));
+                }
+                fed.extend(searcher.finish());
+                let actual = char_matches(t, fed.into_iter());
+                assert_eq!(osa, actual, "{}: osa_searcher({:?}, {:?})", ctx, p, t);
+                let actual = pattern.lev_reader(t.as_bytes(), k).map(Result::unwrap);
+                let actual = char_matches(t, actual);
+                assert_eq!(lev, actual, "{}: lev_reader({:?}, {:?})", ctx, p, t);
+            });
+            for &engine in ENGINES.iter() {
+                let builder = builder.clone().engine(engine);
+                if engine == Engine::Myers && start == Boundary::Word {
+                    let err = Some(Error::UnsupportedBounds);
+                    assert_eq!(builder.build().err(), err, "{} {:?}", ctx, engine);
+                    continue;
+                }
+                let pattern = builder.build().unwrap();
+                let ctx = format!("{} {:?} {:?}..{:?}", ctx, engine, start, end);
+                let actual = char_matches(t, pattern.lev(t, k));
+                assert_eq!(lev, actual, "{}: lev({:?}, {:?}, {})", ctx, p, t, k);
+                let actual = char_matches(t, pattern.osa(t, k));
+                assert_eq!(osa, actual, "{}: osa({:?}, {:?}, {})", ctx, p, t, k);
+            }
+
+            let pattern = builder.build().unwrap();
+            let min_length = cmp::min(2, p.chars().count());
+            let base = ref_prefix(p, t, k, min_length, Some((start, end)));
+            let actual = pattern
+                .lev_prefix(t, k, min_length)
+                .map(|(consumed, m)| (consumed, Match::from(m)))
+                .collect::<Vec<_>>();
+            assert_eq!(
+                base, actual,
+                "{} {:?}..{:?}: lev_prefix({:?}, {:?}, {})",
+                ctx, start, end, p, t, k
+            );
+            let budget = EditCounts {
+                insertions: cmp::min(k, 3),
+                deletions: 1,
+                substitutions: 1,
+                transpositions: 1,
+            };
+            let base = ref_budgeted(p, t, budget, start, end);
+            let actual = pattern
+                .budgeted(t, budget)
+                .unwrap()
+                .map(BudgetMatch::from)
+                .collect::<Vec<_>>();
+            assert_eq!(
+                base, actual,
+                "{} {:?}..{:?}: budgeted({:?}, {:?}, {:?})",
+                ctx, start, end, p, t, budget
+            );
+        }
+    }
+}
+
+#[test]
+fn test_bounds() {
+    bounded_test("inside a word", "cat", "concatenate", 1);
+    bounded_test("whole words", "cat", "a cat, concat, cats", 1);
+    bounded_test("insertion before", "cat", "x cat", 2);
+    bounded_test("at the edges", "abc", "abcxabc", 1);
+    bounded_test("transposed", "abcd", "ab bacd acbd", 1);
+    bounded_test("distance past end", "ab", "x ab y", 4);
+    bounded_test("unicode", "жёлтый", "жолтый, жёлтые", 2);
+    bounded_test("empty text", "abc", "", 1);
+    bounded_test("long", &"abcdefghij".repeat(7), &"abcdefghij".repeat(8), 3);
+
+    // Myers can only start matches anywhere or at the start of the text.
+    let builder = PatternBuilder::new("abc").match_start(Boundary::Word);
+    let pattern = builder.clone().engine(Engine::Myers).build();
+    assert_eq!(pattern.err(), Some(Error::UnsupportedBounds));
+    assert_eq!(builder.build().unwrap().engine_for(4), Engine::Dynamic);
+    let builder = PatternBuilder::new("abc").match_start(Boundary::Text);
+    assert_eq!(builder.build().unwrap().engine_for(4), Engine::Myers);
+
+    // Spans only start where a match is allowed to.
+    let pattern = PatternBuilder::new("cat")
+        .match_start(Boundary::Word)
+        .build()
+        .unwrap();
+    assert_eq!(starts(pattern.lev_spans("xcat cat", 1)), vec![0, 5, 5]);
+    let pattern = PatternBuilder::new("id")
+        .match_start(Boundary::Word)
+        .match_end(Boundary::Word)
+        .word_chars(|c| c.is_alphanumeric() || c == '_')
+        .build()
+        .unwrap();
+    assert_eq!(starts(pattern.lev_spans("user_id id", 0)), vec![8]);
+    // A max distance past what any match could need doesn't cost anything
+    // extra.
+    bounded_test("no max distance", "cat", "a cat, concat, cats", usize::MAX);
+    let pattern = PatternBuilder::new("cat")
+        .match_start(Boundary::Word)
+        .build()
+        .unwrap();
+    let text = "concatenate ".repeat(10000);
+    assert_eq!(pattern.lev(&text, usize::MAX).count(), text.len());
+    // Nor when the start of the text is the only place a match can start,
+    // since then it can use Myers.
+    let pattern = PatternBuilder::new("cat")
+        .match_start(Boundary::Text)
+        .build()
+        .unwrap();
+    assert_eq!(pattern.lev(&text, usize::MAX).count(), text.len());
+    // Deleting the whole pattern after the last character isn't a match.
+    bounded_test("only empty", "ab", "xyz-", 2);
+    let pattern = PatternBuilder::new("ab")
+        .match_start(Boundary::Word)
+        .build()
+        .unwrap();
+    assert_eq!(starts(pattern.lev_spans("xyz-", 2)), vec![0, 0]);
+}
+
+#[test]
+fn test_engines() {
+    let pattern = Pattern::new("hello").unwrap();
+    assert_eq!(pattern.engine_for(0), Engine::Exact);
+    assert_eq!(pattern.engine_for(2), Engine::Static);
+    assert_eq!(pattern.engine_for(3), Engine::Myers);
+    assert_eq!(pattern.engine_for(9), Engine::Myers);
+    // Wide masks make Myers slower, so the static arrays last a bit longer.
+    let pattern = Pattern::new(&"a".repeat(100)).unwrap();
+    assert_eq!(pattern.engine_for(3), Engine::Static);
+    assert_eq!(pattern.engine_for(4), Engine::Myers);
+
+    // Forced engines fall back when they can't handle the distance.
+    let forced = |engine, k| {
+        let pattern = PatternBuilder::new("hello").engine(engine).build().unwrap();
+        pattern.engine_for(k)
+    };
+    assert_eq!(forced(Engine::Exact, 0), Engine::Exact);
+    assert_eq!(forced(Engine::Exact, 1), Engine::Dynamic);
+    assert_eq!(forced(Engine::Static, 3), Engine::Static);
+    assert_eq!(forced(Engine::Static, 4), Engine::Dynamic);
+    // Distances past the pattern length are the same as the length.
+    assert_eq!(forced(Engine::Static, 9), Engine::Dynamic);
+    assert_eq!(forced(Engine::Dynamic, 0), Engine::Dynamic);
+    assert_eq!(forced(Engine::Myers, 0), Engine::Myers);
+}
+
+// Every iterator a pattern hands out can be moved to another thread. This
+// only needs to compile.
+#[test]
+fn test_send() {
+    fn assert_send<T: Send>(_: T) {}
+    let pattern = Pattern::new("abc").unwrap();
+    let text = "xabcx";
+    assert_send(pattern.find(text));
+    assert_send(pattern.lev(text, 1));
+    assert_send(pattern.osa(text, 1));
+    assert_send(pattern.lev_myers(text, 1).unwrap());
+    assert_send(pattern.osa_myers(text, 1).unwrap());
+    assert_send(pattern.lev_prefix(text, 1, 2));
+    assert_send(pattern.damerau(text, 1));
+    assert_send(pattern.budgeted(text, EditCounts::default()).unwrap());
+    assert_send(pattern.hamming(text, 1));
+    assert_send(pattern.lev_best(text, 1));
+    assert_send(pattern.osa_best(text, 1));
+    assert_send(pattern.lev_spans(text, 1));
+    assert_send(pattern.osa_spans(text, 1));
+    assert_send(pattern.lev_static(text, StaticMaxDistance::One));
+    assert_send(pattern.osa_static(text, StaticMaxDistance::One));
+    assert_send(pattern.hamming_static(text, StaticMaxDistance::One));
+    assert_send(pattern.lev_searcher(1));
+    assert_send(pattern.lev_reader(text.as_bytes(), 1));
+    assert_send(pattern.osa_reader(text.as_bytes(), 1));
+}
+
+#[test]
+fn test_errors() {
+    assert_eq!(Pattern::new("").err(), Some(Error::EmptyPattern));
+    assert_eq!(
+        Pattern::new(&"a".repeat(256)).err(),
+        Some(Error::PatternTooLong { len: 256, max: 255 })
+    );
+    let masks = vec![0usize; 64];
+    assert_eq!(
+        find(masks.into_iter(), 64).err(),
+        Some(Error::PatternTooLong { len: 64, max: 63 })
+    );
+    assert_eq!(
+        PatternBuilder::new("ab[z-a]").syntax(true).build().err(),
+        Some(Error::InvalidSyntax { position: 2 })
+    );
+    // Huge repeat counts fail before anything gets repeated.
+    assert_eq!(
+        PatternBuilder::new("a{18446744073709551615}")
+            .syntax(true)
+            .build()
+            .err(),
+        Some(Error::PatternTooLong {
+            len: usize::MAX,
+            max: MAX_PATTERN_LENGTH
+        })
+    );
+    assert_eq!(
+        PatternBuilder::new("a{200000000}")
+            .syntax(true)
+            .build()
+            .err(),
+        Some(Error::PatternTooLong {
+            len: 200000000,
+            max: MAX_PATTERN_LENGTH
+        })
+    );
+    assert_eq!(
+        PatternBuilder::new("ab{18446744073709551615}")
+            .syntax(true)
+            .build()
+            .err(),
+        Some(Error::InvalidSyntax { position: 2 })
+    );
+    assert_eq!(
+        PatternSet::new(["abc", ""]).err(),
+        Some(Error::EmptyPattern)
+    );
+    assert_eq!(Error::EmptyPattern.to_string(), "pattern is empty");
+    assert_eq!(
+        Error::PatternTooLong { len: 256, max: 255 }.to_string(),
+        "pattern is 256 characters long, but at most 255 are allowed"
+    );
+    assert_eq!(
+        Error::InvalidSyntax { position: 2 }.to_string(),
+        "invalid pattern syntax at character 2"
+    );
+    assert_eq!(
+        Error::BudgetTooLarge {
+            states: 70000,
+            max: 65536
+        }
+        .to_string(),
+        "edit budget allows 70000 combinations of edits, but at most 65536 are allowed"
+    );
+}
+
 #[quickcheck]
 fn qc_find(pattern: String, text: String) -> TestResult {
     if !pattern_length_is_valid(pattern.chars().count()) {
         return TestResult::discard();
     }
-    let a = Pattern::new(&pattern)
-        .unwrap()
-        .find(&text)
-        .collect::<Vec<_>>();
     let b = bref::find(&pattern, &text).unwrap();
-    TestResult::from_bool(a == b)
+    let mut ok = true;
+    for_each_width!(&pattern, |p, _width| {
+        ok &= starts(p.find(&text)) == b;
+    });
+    TestResult::from_bool(ok)
 }
 
 #[quickcheck]
@@ -111,12 +1780,20 @@ fn qc_lev(pattern: String, text: String, k: usize) -> TestResult {
     if !pattern_length_is_valid(pattern.chars().count()) {
         return TestResult::discard();
     }
-    let a = Pattern::new(&pattern)
-        .unwrap()
-        .lev(&text, k)
-        .collect::<Vec<_>>();
     let b = ref_result_convert(bref::lev(&pattern, &text, k)).unwrap();
-    TestResult::from_bool(a == b)
+    let mut ok = true;
+    for_each_width!(&pattern, |p, _width| {
+        ok &= char_matches(&text, p.lev(&text, k)) == b;
+        ok &= char_matches(&text, p.lev_myers(&text, k).unwrap()) == b;
+    });
+    for &engine in ENGINES.iter() {
+        let p = PatternBuilder::new(&pattern)
+            .engine(engine)
+            .build()
+            .unwrap();
+        ok &= char_matches(&text, p.lev(&text, k)) == b;
+    }
+    TestResult::from_bool(ok)
 }
 
 #[quickcheck]
@@ -124,12 +1801,297 @@ fn qc_osa(pattern: String, text: String, k: usize) -> TestResult {
     if !pattern_length_is_valid(pattern.chars().count()) {
         return TestResult::discard();
     }
-    let a = Pattern::new(&pattern)
-        .unwrap()
-        .osa(&text, k)
-        .collect::<Vec<_>>();
     let b = ref_result_convert(bref::osa(&pattern, &text, k)).unwrap();
-    TestResult::from_bool(a == b)
+    let mut ok = true;
+    for_each_width!(&pattern, |p, _width| {
+        ok &= char_matches(&text, p.osa(&text, k)) == b;
+        ok &= char_matches(&text, p.osa_myers(&text, k).unwrap()) == b;
+    });
+    for &engine in ENGINES.iter() {
+        let p = PatternBuilder::new(&pattern)
+            .engine(engine)
+            .build()
+            .unwrap();
+        ok &= char_matches(&text, p.osa(&text, k)) == b;
+    }
+    TestResult::from_bool(ok)
+}
+
+#[quickcheck]
+fn qc_damerau(pattern: String, text: String, k: usize) -> TestResult {
+    if !pattern_length_is_valid(pattern.chars().count()) {
+        return TestResult::discard();
+    }
+    // The baseline is slow, so keep the text short.
+    let text = text.chars().take(30).collect::<String>();
+    damerau_test("qc_damerau", &pattern, &text, k % 4);
+    TestResult::passed()
+}
+
+#[quickcheck]
+fn qc_budgeted(pattern: String, text: String, budget: (u8, u8, u8, u8)) -> TestResult {
+    if !pattern_length_is_valid(pattern.chars().count()) {
+        return TestResult::discard();
+    }
+    let budget = EditCounts {
+        insertions: budget.0 as usize % 3,
+        deletions: budget.1 as usize % 3,
+        substitutions: budget.2 as usize % 3,
+        transpositions: budget.3 as usize % 2,
+    };
+    budgeted_test("qc_budgeted", &pattern, &text, budget);
+    TestResult::passed()
+}
+
+#[quickcheck]
+fn qc_hamming(pattern: String, text: String, k: usize) -> TestResult {
+    if !pattern_length_is_valid(pattern.chars().count()) {
+        return TestResult::discard();
+    }
+    hamming_test("qc_hamming", &pattern, &text, k);
+    TestResult::passed()
+}
+
+#[quickcheck]
+fn qc_distance(a: String, b: String, k: usize) -> TestResult {
+    let k = k % 8;
+    let lev = bref::baseline::distance(&a, &b, DistanceFn::Levenshtein);
+    let osa = bref::baseline::distance(&a, &b, DistanceFn::OptimalStringAlignment);
+    // The fallback for long strings should agree too, on strings short
+    // enough for the reference.
+    let within = |d: usize| Some(d).filter(|&d| d <= k);
+    TestResult::from_bool(
+        levenshtein_distance(&a, &b) == lev
+            && osa_distance(&a, &b) == osa
+            && within_distance(&a, &b, k) == (lev <= k)
+            && distance::table(&a, &b, usize::MAX, false) == Some(lev)
+            && distance::table(&a, &b, usize::MAX, true) == Some(osa)
+            && distance::table(&a, &b, k, false) == within(lev)
+            && distance::table(&a, &b, k, true) == within(osa),
+    )
+}
+
+#[quickcheck]
+fn qc_prefix(pattern: String, text: String, k: usize, min_length: usize) -> TestResult {
+    if !pattern_length_is_valid(pattern.chars().count()) {
+        return TestResult::discard();
+    }
+    let (k, min_length) = (k % 4, min_length % 8);
+    let base = ref_prefix(&pattern, &text, k, min_length, None);
+    let p = Pattern::new(&pattern).unwrap();
+    let actual = p
+        .lev_prefix(&text, k, min_length)
+        .map(|(consumed, m)| (consumed, Match::from(m)));
+    TestResult::from_bool(actual.eq(base))
+}
+
+#[quickcheck]
+fn qc_bounds(pattern: String, text: String, k: usize, bounds: (u8, u8)) -> TestResult {
+    if !pattern_length_is_valid(pattern.chars().count()) {
+        return TestResult::discard();
+    }
+    let k = k % 4;
+    let start = BOUNDARIES[bounds.0 as usize % 3];
+    let end = BOUNDARIES[bounds.1 as usize % 3];
+    let p = PatternBuilder::new(&pattern)
+        .match_start(start)
+        .match_end(end)
+        .build()
+        .unwrap();
+    let lev = ref_bounded(&pattern, &text, k, DistanceFn::Levenshtein, start, end);
+    let osa = ref_bounded(
+        &pattern,
+        &text,
+        k,
+        DistanceFn::OptimalStringAlignment,
+        start,
+        end,
+    );
+    TestResult::from_bool(
+        p.lev(&text, k).map(Match::from).eq(lev) && p.osa(&text, k).map(Match::from).eq(osa),
+    )
+}
+
+#[quickcheck]
+fn qc_searcher(pattern: String, text: String, k: usize, splits: Vec<usize>) -> TestResult {
+    if !pattern_length_is_valid(pattern.chars().count()) {
+        return TestResult::discard();
+    }
+    let len = text.chars().count();
+    let mut splits = splits
+        .into_iter()
+        .map(|s| s % (len + 1))
+        .collect::<Vec<_>>();
+    splits.sort();
+    searcher_test("qc_searcher", &pattern, &text, k % 4, &splits);
+    TestResult::passed()
+}
+
+#[quickcheck]
+fn qc_spans(pattern: String, text: String, k: usize) -> TestResult {
+    if !pattern_length_is_valid(pattern.chars().count()) {
+        return TestResult::discard();
+    }
+    let k = k % 4;
+    let p = Pattern::new(&pattern).unwrap();
+    let lev = p.lev(&text, k).collect::<Vec<_>>();
+    let osa = p.osa(&text, k).collect::<Vec<_>>();
+    TestResult::from_bool(
+        ref_spans(&pattern, &text, &lev, DistanceFn::Levenshtein)
+            == p.lev_spans(&text, k).collect::<Vec<_>>()
+            && ref_spans(&pattern, &text, &osa, DistanceFn::OptimalStringAlignment)
+                == p.osa_spans(&text, k).collect::<Vec<_>>(),
+    )
+}
+
+#[quickcheck]
+fn qc_local_minima(matches: Vec<(u8, bool)>) -> bool {
+    // Build a stream of matches with random distances and random gaps.
+    let mut end = 0;
+    let matches = matches
+        .into_iter()
+        .map(|(distance, gap)| {
+            end += if gap { 2 } else { 1 };
+            Match {
+                distance: (distance % 4) as usize,
+                end,
+            }
+        })
+        .collect::<Vec<_>>();
+    local_minima(matches.iter().cloned()).collect::<Vec<_>>() == ref_local_minima(&matches)
+}
+
+#[quickcheck]
+fn qc_bytes(pattern: Vec<u8>, text: Vec<u8>, k: usize) -> TestResult {
+    if !pattern_length_is_valid(pattern.len()) {
+        return TestResult::discard();
+    }
+    bytes_test("qc_bytes", &pattern, &text, k % 4);
+    TestResult::passed()
+}
+
+// Case insensitive matching should be exactly the same as folding the case of
+// the pattern and the text before searching.
+#[quickcheck]
+fn qc_case_insensitive(pattern: String, text: String, k: usize) -> TestResult {
+    if !pattern_length_is_valid(pattern.chars().count()) {
+        return TestResult::discard();
+    }
+    let k = k % 4;
+    let fold = |s: &str| s.chars().map(case::fold).collect::<String>();
+    let a = PatternBuilder::new(&pattern)
+        .case_insensitive(true)
+        .build()
+        .unwrap();
+    let b = Pattern::new(&fold(&pattern)).unwrap();
+    let text_folded = fold(&text);
+    // Folding can change how many bytes a character takes, so only the
+    // positions have to line up.
+    let span = |m: MatchSpan| (m.start, m.end);
+    let text_match = |m: TextMatch| (m.distance, m.end);
+    TestResult::from_bool(
+        a.find(&text).map(span).eq(b.find(&text_folded).map(span))
+            && a.lev(&text, k)
+                .map(text_match)
+                .eq(b.lev(&text_folded, k).map(text_match))
+            && a.osa(&text, k)
+                .map(text_match)
+                .eq(b.osa(&text_folded, k).map(text_match)),
+    )
+}
+
+#[quickcheck]
+fn qc_edits(pattern: String, text: String, k: usize) -> TestResult {
+    if !pattern_length_is_valid(pattern.chars().count()) {
+        return TestResult::discard();
+    }
+    edits_test("qc_edits", &pattern, &text, k % 4);
+    TestResult::passed()
+}
+
+// The multi-word adapters should give exactly the same results as the single
+// word ones for patterns that fit in either.
+#[quickcheck]
+fn qc_wide(pattern: String, text: String, k: usize) -> TestResult {
+    if !pattern_length_is_valid(pattern.chars().count()) {
+        return TestResult::discard();
+    }
+    let len = pattern.chars().count();
+    let word = CharMasks::<usize>::new(&literal(&pattern), false, &[]);
+    let wide = CharMasks::<Wide<2>>::new(&literal(&pattern), false, &[]);
+
+    let a = find(mask_iter(&word, text.chars()), len).unwrap();
+    let b = find(mask_iter(&wide, text.chars()), len).unwrap();
+    if !a.eq(b) {
+        return TestResult::failed();
+    }
+    let a = levenshtein(mask_iter(&word, text.chars()), len, k).unwrap();
+    let b = levenshtein(mask_iter(&wide, text.chars()), len, k).unwrap();
+    if !a.eq(b) {
+        return TestResult::failed();
+    }
+    let a = optimal_string_alignment(mask_iter(&word, text.chars()), len, k).unwrap();
+    let b = optimal_string_alignment(mask_iter(&wide, text.chars()), len, k).unwrap();
+    TestResult::from_bool(a.eq(b))
+}
+
+// Random classes should match the same as the dynamic programming reference.
+#[quickcheck]
+fn qc_classes(spec: Vec<(u8, char, char)>, text: String, k: usize) -> TestResult {
+    if !pattern_length_is_valid(spec.len()) {
+        return TestResult::discard();
+    }
+    let classes = spec
+        .into_iter()
+        .map(|(kind, a, b)| {
+            let (a, b) = (cmp::min(a, b), cmp::max(a, b));
+            match kind % 4 {
+                0 => CharClass::Char(a),
+                1 => CharClass::Set(vec![a..=b]),
+                2 => CharClass::NotSet(vec![a..=b]),
+                _ => CharClass::Any,
+            }
+        })
+        .collect::<Vec<_>>();
+    classes_test("qc_classes", &classes, &text, k % 4);
+    TestResult::passed()
+}
+
+// A text wildcard should work exactly like adding it to every position of the
+// pattern.
+#[quickcheck]
+fn qc_text_wildcards(pattern: String, text: String, wildcard: char, k: usize) -> TestResult {
+    if !pattern_length_is_valid(pattern.chars().count()) {
+        return TestResult::discard();
+    }
+    let k = k % 4;
+    let a = PatternBuilder::new(&pattern)
+        .text_wildcards(vec![wildcard])
+        .build()
+        .unwrap();
+    let classes = pattern
+        .chars()
+        .map(|c| CharClass::Set(vec![c..=c, wildcard..=wildcard]))
+        .collect::<Vec<_>>();
+    let b = PatternBuilder::from_classes(&classes).build().unwrap();
+    TestResult::from_bool(
+        a.find(&text).eq(b.find(&text))
+            && a.lev(&text, k).eq(b.lev(&text, k))
+            && a.osa(&text, k).eq(b.osa(&text, k)),
+    )
+}
+
+#[quickcheck]
+fn qc_pattern_set(patterns: Vec<String>, text: String, k: usize) -> TestResult {
+    if !patterns
+        .iter()
+        .all(|p| pattern_length_is_valid(p.chars().count()))
+    {
+        return TestResult::discard();
+    }
+    let patterns = patterns.iter().map(|p| p.as_str()).collect::<Vec<_>>();
+    pattern_set_test("qc_pattern_set", &patterns, &text, k % 4);
+    TestResult::passed()
 }
 
 fn ref_result_convert(r: bref::BitapResult) -> Result<Vec<Match>, &'static str> {